@@ -1,22 +1,136 @@
-use bevy::{time::{Time, Timer}, sprite::TextureAtlasSprite, prelude::{Res, Component, Deref, DerefMut}};
+use bevy::{
+    prelude::{AssetServer, Assets, Component, Entity, EventWriter, Handle, Query, Res, ResMut, Vec2},
+    sprite::{TextureAtlas, TextureAtlasSprite},
+    time::{Time, Timer, TimerMode},
+};
 
-#[derive(Component, Deref, DerefMut)]
-pub struct AnimationTimer(pub Timer);
+/// A texture cut into `columns` frames of `frame_size`, side by side.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct SpriteSheet {
+    pub texture: &'static str,
+    pub frame_size: Vec2,
+    pub columns: usize,
+}
 
-#[derive(Component)]
-pub struct AnimationIndices {
+impl SpriteSheet {
+    pub fn texture_atlas(&self, asset_server: &AssetServer) -> TextureAtlas {
+        TextureAtlas::from_grid(asset_server.load(self.texture), self.frame_size, self.columns, 1, None, None)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AnimationMode {
+    /// Starts over from the first frame after the last one.
+    Loop,
+    /// Stops on the last frame and sends an `AnimationFinishedEvent`.
+    Once,
+}
+
+/// The frames `first..=last` of `sheet`, each shown for `frame_duration`
+/// seconds.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct AnimationClip {
+    pub name: &'static str,
+    pub sheet: SpriteSheet,
     pub first: usize,
     pub last: usize,
+    pub frame_duration: f32,
+    pub mode: AnimationMode,
+}
+
+impl AnimationClip {
+    /// A clip that holds frame `index` of `sheet` and never finishes.
+    pub fn still(name: &'static str, sheet: SpriteSheet, index: usize) -> Self {
+        AnimationClip {
+            name,
+            sheet,
+            first: index,
+            last: index,
+            frame_duration: 0.0,
+            mode: AnimationMode::Loop,
+        }
+    }
+}
+
+/// Plays a clip on the entity's sprite. The entity's texture atlas must start
+/// out as the clip's sheet; later clips swap it as needed.
+#[derive(Component)]
+pub struct Animation {
+    clip: AnimationClip,
+    timer: Timer,
+    is_finished: bool,
+    /// Set by `play` until `animate_sprites` shows the new clip's first frame.
+    is_starting: bool,
+    is_sheet_changed: bool,
 }
 
-pub fn animate(
-    time: &Res<Time>,
-    timer: &mut AnimationTimer,
-    sprite: &mut TextureAtlasSprite,
-    animation_indices: &AnimationIndices,
+impl Animation {
+    pub fn new(clip: AnimationClip) -> Self {
+        Animation {
+            clip,
+            timer: Timer::from_seconds(clip.frame_duration, TimerMode::Repeating),
+            is_finished: false,
+            is_starting: true,
+            is_sheet_changed: false,
+        }
+    }
+
+    /// Switches to `clip` from its first frame. Playing the clip that is
+    /// already on carries on with it instead.
+    pub fn play(&mut self, clip: AnimationClip) {
+        if clip == self.clip {
+            return;
+        }
+
+        let is_sheet_changed = self.is_sheet_changed || clip.sheet != self.clip.sheet;
+        *self = Animation::new(clip);
+        self.is_sheet_changed = is_sheet_changed;
+    }
+}
+
+/// Sent once a `Once` clip has shown its last frame for its full duration.
+pub struct AnimationFinishedEvent {
+    pub entity: Entity,
+    pub clip: &'static str,
+}
+
+pub fn animate_sprites(
+    time: Res<Time>,
+    asset_server: Res<AssetServer>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    mut query: Query<(Entity, &mut TextureAtlasSprite, &mut Handle<TextureAtlas>, &mut Animation)>,
+    mut animation_finished_event_writer: EventWriter<AnimationFinishedEvent>,
 ) {
-    timer.tick(time.delta());
-    if timer.just_finished() {
-        sprite.index = (sprite.index + 1) % (animation_indices.last + 1);
+    for (entity, mut sprite, mut texture_atlas, mut animation) in query.iter_mut() {
+        let clip = animation.clip;
+
+        if animation.is_starting {
+            if animation.is_sheet_changed {
+                *texture_atlas = texture_atlases.add(clip.sheet.texture_atlas(&asset_server));
+            }
+            sprite.index = clip.first;
+            animation.is_starting = false;
+            animation.is_sheet_changed = false;
+        }
+
+        if animation.is_finished || clip.first == clip.last {
+            continue;
+        }
+
+        animation.timer.tick(time.delta());
+        for _ in 0..animation.timer.times_finished_this_tick() {
+            if sprite.index < clip.last {
+                sprite.index += 1;
+            } else if clip.mode == AnimationMode::Loop {
+                sprite.index = clip.first;
+            } else {
+                animation.is_finished = true;
+                animation_finished_event_writer.send(AnimationFinishedEvent {
+                    entity,
+                    clip: clip.name,
+                });
+                break;
+            }
+        }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,485 @@
+use bevy::{
+    ecs::system::EntityCommands,
+    prelude::{
+        Added, AssetServer, Assets, Commands, Component, Entity, EventReader, Query, Res, ResMut,
+        SpriteBundle, Transform, Vec2, Vec3, With, Without, default,
+    },
+    sprite::{TextureAtlas, TextureAtlasSprite},
+    time::{Time, Timer, TimerMode},
+};
+use rand::Rng;
+use serde::Deserialize;
+
+use crate::{
+    collision::EnemyCollisionEvent,
+    enemy::{spawn_enemy, Enemy, EnemyType},
+    game_state::LevelScoped,
+    hammer::{get_hammer_bundle, Hammer},
+    mario::Mario,
+    physics::Velocity,
+    shell::Shell,
+    tilemap::{TileMap, TILE_SIZE},
+};
+
+/// The highest platform a `Jumper` jumps up to, in tiles above its feet.
+const JUMPER_MAX_TILES: i32 = 4;
+/// How quickly a `Chaser` closes the gap to Mario, per second, before it
+/// reaches its top speed.
+const CHASER_GAIN: f32 = 4.0;
+const PIPE_COVER_SIZE: Vec2 = Vec2::new(32.0, 32.0);
+/// A `PipeDweller` stays in its pipe while Mario is this close to it, so he
+/// can always stand on the pipe.
+const PIPE_DWELLER_HOLD_DISTANCE: f32 = 28.0;
+
+/// One piece of how an enemy moves or attacks. Each enemy type has a default
+/// set, which a level can replace per enemy, e.g.
+/// `behaviors: [Walker(speed: 20.0, turn_at_ledges: true)]`.
+#[derive(Deserialize, Clone, Copy)]
+pub enum EnemyBehavior {
+    Walker {
+        speed: f32,
+        #[serde(default)]
+        turn_at_ledges: bool,
+        #[serde(default)]
+        pace_distance: Option<f32>,
+    },
+    Jumper {
+        speed: f32,
+        interval: f32,
+    },
+    Flyer,
+    Bouncer {
+        speed: f32,
+    },
+    Shooter {
+        projectile: Projectile,
+        interval: f32,
+        limit: usize,
+    },
+    Chaser {
+        speed: f32,
+    },
+    PipeDweller {
+        speed: f32,
+        wait: f32,
+    },
+}
+
+impl EnemyBehavior {
+    /// Adds the behavior's component to an enemy spawned at `x`, `y`.
+    pub fn insert(&self, enemy: &mut EntityCommands, x: f32, y: f32) {
+        match *self {
+            EnemyBehavior::Walker {
+                speed,
+                turn_at_ledges,
+                pace_distance,
+            } => {
+                enemy.insert(Walker {
+                    speed,
+                    turn_at_ledges,
+                    home_x: x,
+                    pace_distance,
+                });
+            }
+            EnemyBehavior::Jumper { speed, interval } => {
+                enemy.insert(Jumper {
+                    speed,
+                    timer: Timer::from_seconds(interval, TimerMode::Repeating),
+                    drop_to: None,
+                });
+            }
+            EnemyBehavior::Flyer => {
+                enemy.insert(Flyer);
+            }
+            EnemyBehavior::Bouncer { speed } => {
+                enemy.insert(Bouncer { speed });
+            }
+            EnemyBehavior::Shooter {
+                projectile,
+                interval,
+                limit,
+            } => {
+                enemy.insert(Shooter {
+                    projectile,
+                    timer: Timer::from_seconds(interval, TimerMode::Repeating),
+                    limit,
+                });
+            }
+            EnemyBehavior::Chaser { speed } => {
+                enemy.insert(Chaser { speed });
+            }
+            EnemyBehavior::PipeDweller { speed, wait } => {
+                enemy.insert((
+                    PipeDweller {
+                        speed,
+                        top: y,
+                        phase: PipeDwellerPhase::Out,
+                        timer: Timer::from_seconds(wait, TimerMode::Once),
+                    },
+                    NoTileMovement,
+                ));
+            }
+        }
+    }
+}
+
+/// What a `Shooter` throws.
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum Projectile {
+    Hammer,
+    Spiny,
+}
+
+/// Walks the way the enemy faces, turning around at walls. It also turns at
+/// ledges if `turn_at_ledges`, and once it is `pace_distance` from `home_x`.
+#[derive(Component)]
+pub struct Walker {
+    pub speed: f32,
+    pub turn_at_ledges: bool,
+    home_x: f32,
+    pace_distance: Option<f32>,
+}
+
+/// Every so often while on the ground, jumps up through the platform above
+/// or drops down through the one it stands on.
+#[derive(Component)]
+pub struct Jumper {
+    speed: f32,
+    timer: Timer,
+    /// While dropping through a platform, the Y it falls freely down to.
+    drop_to: Option<f32>,
+}
+
+impl Jumper {
+    /// Whether the enemy is on its way up through a platform or down through
+    /// one, so tiles shouldn't stop it.
+    pub fn is_passing_through(&self, velocity: &Velocity) -> bool {
+        velocity.y > 0.0 || self.drop_to.is_some()
+    }
+}
+
+/// Ignores gravity and keeps its height.
+#[derive(Component)]
+pub struct Flyer;
+
+/// Added by behaviors that move the enemy themselves, so `move_enemy` leaves
+/// it alone: no gravity, no tile collisions and no turning at walls.
+#[derive(Component)]
+pub struct NoTileMovement;
+
+/// Hops back up at `speed` whenever it lands, like a winged Koopa.
+#[derive(Component)]
+pub struct Bouncer {
+    speed: f32,
+}
+
+/// Faces Mario and throws `projectile` at him on a timer, holding off while
+/// `limit` of them are already about. Hammers are counted per thrower and
+/// Spinies across the level.
+#[derive(Component)]
+pub struct Shooter {
+    projectile: Projectile,
+    timer: Timer,
+    limit: usize,
+}
+
+/// Keeps moving towards Mario's X.
+#[derive(Component)]
+pub struct Chaser {
+    speed: f32,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum PipeDwellerPhase {
+    Hidden,
+    Rising,
+    Out,
+    Sinking,
+}
+
+/// Lives in a pipe whose mouth is just below `top`, its Y when fully out,
+/// like a Piranha Plant. It waits out of the pipe, sinks until it is hidden,
+/// waits again and rises back out. It ignores gravity and tiles.
+#[derive(Component)]
+pub struct PipeDweller {
+    speed: f32,
+    top: f32,
+    phase: PipeDwellerPhase,
+    timer: Timer,
+}
+
+pub fn walk_enemies(
+    mut query: Query<(&Transform, &mut Velocity, &mut TextureAtlasSprite, &mut Enemy, &Walker), Without<Shell>>,
+) {
+    for (transform, mut velocity, mut sprite, mut enemy, walker) in query.iter_mut() {
+        if !enemy.is_active {
+            continue;
+        }
+
+        if let Some(pace_distance) = walker.pace_distance {
+            if transform.translation.x >= walker.home_x + pace_distance {
+                enemy.go_right = false;
+            } else if transform.translation.x <= walker.home_x - pace_distance {
+                enemy.go_right = true;
+            }
+        }
+
+        velocity.x = if enemy.go_right { walker.speed } else { -walker.speed };
+        sprite.flip_x = !enemy.go_right;
+    }
+}
+
+pub fn chase_mario(
+    mario_query: Query<&Transform, With<Mario>>,
+    mut query: Query<(&Transform, &mut Velocity, &Enemy, &Chaser), Without<Mario>>,
+) {
+    let mario_x = match mario_query.get_single() {
+        Ok(mario_transform) => mario_transform.translation.x,
+        Err(_) => return,
+    };
+
+    for (transform, mut velocity, enemy, chaser) in query.iter_mut() {
+        if enemy.is_active {
+            velocity.x = ((mario_x - transform.translation.x) * CHASER_GAIN).clamp(-chaser.speed, chaser.speed);
+        }
+    }
+}
+
+/// Runs after `move_enemy`, so a Y velocity of zero means the enemy is on the
+/// ground.
+pub fn jump_enemies(
+    time: Res<Time>,
+    tilemap: Res<TileMap>,
+    mut query: Query<(&Transform, &mut Velocity, &Enemy, &mut Jumper)>,
+) {
+    let mut rng = rand::thread_rng();
+
+    for (transform, mut velocity, enemy, mut jumper) in query.iter_mut() {
+        if !enemy.is_active {
+            continue;
+        }
+
+        if let Some(drop_to) = jumper.drop_to {
+            if transform.translation.y <= drop_to {
+                jumper.drop_to = None;
+            }
+            continue;
+        }
+
+        if velocity.y != 0.0 || !jumper.timer.tick(time.delta()).just_finished() {
+            continue;
+        }
+
+        let size = enemy.enemy_type.size();
+        let column = tilemap.column_at(transform.translation.x);
+        let floor_row = tilemap.row_at(transform.translation.y - size.y / 2.0 - 1.0);
+        let can_jump_up = (2..=JUMPER_MAX_TILES).any(|tiles| {
+            tilemap.is_solid(column, floor_row - tiles)
+                && !tilemap.is_solid(column, floor_row - tiles - 1)
+        });
+        let can_drop = !tilemap.is_solid(column, floor_row + 1)
+            && (floor_row + 2..tilemap.row_at(tilemap.bottom()))
+                .any(|row| tilemap.is_solid(column, row));
+
+        if can_jump_up && (!can_drop || rng.gen_bool(0.5)) {
+            velocity.y = jumper.speed;
+        } else if can_drop {
+            jumper.drop_to = Some(transform.translation.y - TILE_SIZE);
+        }
+    }
+}
+
+/// Runs after `move_enemy`, like `jump_enemies`.
+pub fn bounce_enemies(
+    tilemap: Res<TileMap>,
+    mut query: Query<(&Transform, &mut Velocity, &Enemy, &Bouncer), Without<Shell>>,
+) {
+    for (transform, mut velocity, enemy, bouncer) in query.iter_mut() {
+        let size = enemy.enemy_type.size();
+        let is_on_ground = velocity.y == 0.0
+            && tilemap.overlaps_solid(transform.translation - Vec3::new(0.0, 1.0, 0.0), size);
+
+        if enemy.is_active && is_on_ground {
+            velocity.y = bouncer.speed;
+        }
+    }
+}
+
+/// Turns enemies that walk into each other back the way they came. A sliding
+/// shell ploughs through instead; see `shell_hit_enemies`.
+#[allow(clippy::type_complexity)]
+pub fn turn_colliding_enemies(
+    mut enemy_collision_event_reader: EventReader<EnemyCollisionEvent>,
+    mut enemy_query: Query<(&Transform, &mut Enemy, Option<(&Shell, &Velocity)>)>,
+) {
+    for event in enemy_collision_event_reader.iter() {
+        let [(transform, mut enemy, shell), (other_transform, mut other, other_shell)] =
+            match enemy_query.get_many_mut([event.enemy, event.other]) {
+                Ok(enemies) => enemies,
+                Err(_) => continue,
+            };
+
+        if shell.is_some_and(|(_, velocity)| Shell::is_sliding(velocity))
+            || other_shell.is_some_and(|(_, velocity)| Shell::is_sliding(velocity))
+        {
+            continue;
+        }
+
+        enemy.go_right = transform.translation.x >= other_transform.translation.x;
+        other.go_right = !enemy.go_right;
+    }
+}
+
+/// Covers the mouth of each new `PipeDweller`'s pipe with a pipe top, drawn
+/// in front of the enemy so it hides inside. The rest of the pipe is part of
+/// the level background.
+pub fn spawn_pipe_covers(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    query: Query<(&Transform, &Enemy, &PipeDweller), Added<PipeDweller>>,
+) {
+    for (transform, enemy, pipe_dweller) in query.iter() {
+        let mouth_y = pipe_dweller.top - enemy.enemy_type.size().y / 2.0;
+
+        commands.spawn((
+            SpriteBundle {
+                texture: asset_server.load("pipe.png"),
+                transform: Transform::from_xyz(transform.translation.x, mouth_y - PIPE_COVER_SIZE.y / 2.0, 3.5),
+                ..default()
+            },
+            LevelScoped,
+        ));
+    }
+}
+
+pub fn dwell_in_pipes(
+    time: Res<Time>,
+    mario_query: Query<&Transform, With<Mario>>,
+    mut query: Query<(&mut Transform, &Enemy, &mut PipeDweller), Without<Mario>>,
+) {
+    let delta_seconds = time.delta_seconds();
+
+    for (mut transform, enemy, mut pipe_dweller) in query.iter_mut() {
+        if !enemy.is_active {
+            continue;
+        }
+
+        let bottom = pipe_dweller.top - enemy.enemy_type.size().y;
+
+        match pipe_dweller.phase {
+            PipeDwellerPhase::Hidden => {
+                if !pipe_dweller.timer.tick(time.delta()).finished() {
+                    continue;
+                }
+
+                let is_mario_near = mario_query.iter().any(|mario_transform| {
+                    (mario_transform.translation.x - transform.translation.x).abs()
+                        < PIPE_DWELLER_HOLD_DISTANCE
+                });
+                if !is_mario_near {
+                    pipe_dweller.phase = PipeDwellerPhase::Rising;
+                }
+            }
+            PipeDwellerPhase::Rising => {
+                transform.translation.y += pipe_dweller.speed * delta_seconds;
+                if transform.translation.y >= pipe_dweller.top {
+                    transform.translation.y = pipe_dweller.top;
+                    pipe_dweller.phase = PipeDwellerPhase::Out;
+                    pipe_dweller.timer.reset();
+                }
+            }
+            PipeDwellerPhase::Out => {
+                if pipe_dweller.timer.tick(time.delta()).finished() {
+                    pipe_dweller.phase = PipeDwellerPhase::Sinking;
+                }
+            }
+            PipeDwellerPhase::Sinking => {
+                transform.translation.y -= pipe_dweller.speed * delta_seconds;
+                if transform.translation.y <= bottom {
+                    transform.translation.y = bottom;
+                    pipe_dweller.phase = PipeDwellerPhase::Hidden;
+                    pipe_dweller.timer.reset();
+                }
+            }
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn shoot_projectiles(
+    mut commands: Commands,
+    time: Res<Time>,
+    asset_server: Res<AssetServer>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    mario_query: Query<&Transform, With<Mario>>,
+    mut shooter_query: Query<(Entity, &Transform, &mut TextureAtlasSprite, &Enemy, &mut Shooter), Without<Mario>>,
+    hammer_query: Query<&Hammer>,
+    enemy_query: Query<&Enemy>,
+) {
+    let mario_x = match mario_query.get_single() {
+        Ok(mario_transform) => mario_transform.translation.x,
+        Err(_) => return,
+    };
+
+    for (entity, transform, mut sprite, enemy, mut shooter) in shooter_query.iter_mut() {
+        if !enemy.is_active {
+            continue;
+        }
+
+        let face_right = mario_x >= transform.translation.x;
+        sprite.flip_x = !face_right;
+
+        if !shooter.timer.tick(time.delta()).just_finished() {
+            continue;
+        }
+
+        let x = transform.translation.x;
+        let y = transform.translation.y;
+
+        match shooter.projectile {
+            Projectile::Hammer => {
+                let hammers = hammer_query.iter().filter(|hammer| hammer.owner == entity).count();
+                if hammers >= shooter.limit {
+                    continue;
+                }
+
+                let direction_x = if face_right { 1.0 } else { -1.0 };
+                commands.spawn((
+                    get_hammer_bundle(
+                        &asset_server,
+                        &mut texture_atlases,
+                        entity,
+                        face_right,
+                        x + direction_x * 4.0,
+                        y + enemy.enemy_type.size().y / 2.0,
+                    ),
+                    LevelScoped,
+                ));
+            }
+            Projectile::Spiny => {
+                let spinies = enemy_query
+                    .iter()
+                    .filter(|other| other.enemy_type == EnemyType::Spiny)
+                    .count();
+                if spinies >= shooter.limit {
+                    continue;
+                }
+
+                let spiny = spawn_enemy(
+                    &mut commands,
+                    &asset_server,
+                    &mut texture_atlases,
+                    EnemyType::Spiny,
+                    &[],
+                    x,
+                    y,
+                );
+                commands.entity(spiny).insert(Enemy {
+                    go_right: face_right,
+                    is_active: true,
+                    enemy_type: EnemyType::Spiny,
+                });
+            }
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use std::f32::consts::PI;
+
+use bevy::{
+    prelude::{
+        AssetServer, Audio, Commands, Component, Entity, EventReader, EventWriter, Query, Res,
+        Transform, Vec2, Vec3, With, Without,
+    },
+    time::{Time, Timer, TimerMode},
+};
+
+use crate::{
+    collision::{is_standing_on, Collider},
+    enemy::{knock_out_enemy, Enemy, EnemyDead},
+    mushroom::MagicMushroom,
+    physics::Velocity,
+    score::{ScoreEvent, STOMP_POINTS},
+    star::Star,
+};
+
+const BLOCK_SIZE: Vec2 = Vec2::new(16.0, 16.0);
+const BLOCK_BUMP_HEIGHT: f32 = 6.0;
+const BLOCK_BUMP_DURATION: f32 = 0.2;
+/// How fast an item on top of a bumped block is thrown up.
+const ITEM_KNOCK_SPEED: f32 = 200.0;
+
+/// Bounces a block up and back down to `base_y` after Mario hits it. A block
+/// can't be hit again until it has settled.
+#[derive(Component)]
+pub struct BlockBump {
+    base_y: f32,
+    timer: Timer,
+}
+
+impl BlockBump {
+    pub fn new(base_y: f32) -> Self {
+        BlockBump {
+            base_y,
+            timer: Timer::from_seconds(BLOCK_BUMP_DURATION, TimerMode::Once),
+        }
+    }
+}
+
+/// Sent for every block Mario hits from below, whether or not it breaks, so
+/// whatever is standing on it gets knocked off.
+pub struct BlockBumpEvent {
+    pub x: f32,
+    pub y: f32,
+}
+
+pub fn animate_block_bumps(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut Transform, &mut BlockBump)>,
+) {
+    for (entity, mut transform, mut bump) in query.iter_mut() {
+        bump.timer.tick(time.delta());
+        transform.translation.y =
+            bump.base_y + BLOCK_BUMP_HEIGHT * (PI * bump.timer.percent()).sin();
+
+        if bump.timer.finished() {
+            commands.entity(entity).remove::<BlockBump>();
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn knock_out_from_below(
+    mut commands: Commands,
+    mut block_bump_event_reader: EventReader<BlockBumpEvent>,
+    enemy_query: Query<(Entity, &Transform, &Collider), With<Enemy>>,
+    mut magic_mushroom_query: Query<
+        (&Transform, &Collider, &mut Velocity, &mut MagicMushroom),
+        (Without<Star>, Without<Enemy>),
+    >,
+    mut star_query: Query<(&Transform, &Collider, &mut Velocity), (With<Star>, Without<Enemy>)>,
+    mut enemy_dead_event_writer: EventWriter<EnemyDead>,
+    mut score_event_writer: EventWriter<ScoreEvent>,
+    asset_server: Res<AssetServer>,
+    audio: Res<Audio>,
+) {
+    for event in block_bump_event_reader.iter() {
+        let block = Vec3::new(event.x, event.y, 0.0);
+
+        for (enemy, transform, collider) in enemy_query.iter() {
+            if is_standing_on(transform.translation, collider.size, block, BLOCK_SIZE) {
+                let enemy_kill_sound = asset_server.load("stomp.ogg");
+                audio.play(enemy_kill_sound);
+                knock_out_enemy(&mut commands, enemy, transform.translation.x >= event.x);
+                enemy_dead_event_writer.send(EnemyDead);
+                score_event_writer.send(ScoreEvent {
+                    points: STOMP_POINTS,
+                    x: transform.translation.x,
+                    y: transform.translation.y,
+                });
+            }
+        }
+
+        for (transform, collider, mut velocity, mut magic_mushroom) in magic_mushroom_query.iter_mut() {
+            if is_standing_on(transform.translation, collider.size, block, BLOCK_SIZE) {
+                magic_mushroom.go_left = transform.translation.x < event.x;
+                velocity.y = ITEM_KNOCK_SPEED;
+            }
+        }
+
+        for (transform, collider, mut velocity) in star_query.iter_mut() {
+            if is_standing_on(transform.translation, collider.size, block, BLOCK_SIZE) {
+                let direction_x = if transform.translation.x < event.x { -1.0 } else { 1.0 };
+                velocity.x = direction_x * velocity.x.abs();
+                velocity.y = ITEM_KNOCK_SPEED;
+            }
+        }
+    }
+}
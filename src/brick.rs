@@ -0,0 +1,159 @@
+use bevy::{
+    prelude::{
+        AssetServer, Assets, Audio, Commands, Component, Entity, EventReader, EventWriter, Query,
+        Res, ResMut, Transform, Vec2, With, Without, default,
+    },
+    sprite::{SpriteSheetBundle, TextureAtlas, TextureAtlasSprite},
+    time::Time,
+};
+
+use crate::{
+    block_bump::{BlockBump, BlockBumpEvent},
+    collision::Collider,
+    game_state::LevelScoped,
+    mario::{Mario, MarioHeadBumpEvent},
+    physics::{PhysicsConfig, Velocity},
+    score::{ScoreEvent, BRICK_POINTS},
+    tilemap::{Tile, TileMap},
+};
+
+const BRICK_SIZE: Vec2 = Vec2::new(16.0, 16.0);
+const DEBRIS_SIZE: Vec2 = Vec2::new(8.0, 8.0);
+/// Starting velocity of each quarter of a broken brick, in the order of the
+/// quarters in the brick's texture: top left, top right, bottom left and
+/// bottom right.
+const DEBRIS_VELOCITIES: [Vec2; 4] = [
+    Vec2::new(-60.0, 300.0),
+    Vec2::new(60.0, 300.0),
+    Vec2::new(-60.0, 200.0),
+    Vec2::new(60.0, 200.0),
+];
+
+/// Super Mario breaks it from below; small Mario only bumps it.
+#[derive(Component)]
+pub struct BrickBlock;
+
+/// A quarter of a broken brick, falling out of the level.
+#[derive(Component)]
+pub struct Debris;
+
+pub fn get_brick_block_bundle(
+    asset_server: &AssetServer,
+    texture_atlases: &mut Assets<TextureAtlas>,
+    x: f32,
+    y: f32,
+) -> (SpriteSheetBundle, Collider, BrickBlock) {
+    let brick_texture_atlas =
+        TextureAtlas::from_grid(asset_server.load("brickBlock.png"), BRICK_SIZE, 1, 1, None, None);
+
+    (
+        SpriteSheetBundle {
+            texture_atlas: texture_atlases.add(brick_texture_atlas),
+            sprite: TextureAtlasSprite::new(0),
+            transform: Transform::from_xyz(x, y, 3.0),
+            ..default()
+        },
+        Collider::new(BRICK_SIZE),
+        BrickBlock,
+    )
+}
+
+/// `index` is the quarter of the brick the piece shows, which also decides
+/// which way it flies.
+pub fn get_debris_bundle(
+    asset_server: &AssetServer,
+    texture_atlases: &mut Assets<TextureAtlas>,
+    index: usize,
+    x: f32,
+    y: f32,
+) -> (SpriteSheetBundle, Velocity, Debris) {
+    let debris_texture_atlas =
+        TextureAtlas::from_grid(asset_server.load("brickBlock.png"), DEBRIS_SIZE, 2, 2, None, None);
+    let offset = Vec2::new(
+        if index % 2 == 0 { -DEBRIS_SIZE.x } else { DEBRIS_SIZE.x },
+        if index < 2 { DEBRIS_SIZE.y } else { -DEBRIS_SIZE.y },
+    ) / 2.0;
+
+    (
+        SpriteSheetBundle {
+            texture_atlas: texture_atlases.add(debris_texture_atlas),
+            sprite: TextureAtlasSprite::new(index),
+            transform: Transform::from_xyz(x + offset.x, y + offset.y, 4.0),
+            ..default()
+        },
+        Velocity(DEBRIS_VELOCITIES[index]),
+        Debris,
+    )
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn hit_bricks(
+    mut commands: Commands,
+    mut mario_head_bump_event_reader: EventReader<MarioHeadBumpEvent>,
+    mario_query: Query<&Mario>,
+    brick_query: Query<(Entity, &Transform, &Collider), (With<BrickBlock>, Without<BlockBump>)>,
+    mut tilemap: ResMut<TileMap>,
+    mut block_bump_event_writer: EventWriter<BlockBumpEvent>,
+    mut score_event_writer: EventWriter<ScoreEvent>,
+    asset_server: Res<AssetServer>,
+    audio: Res<Audio>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+) {
+    let is_big = mario_query.iter().any(|mario| mario.power.is_big());
+
+    for event in mario_head_bump_event_reader.iter() {
+        // The bump comes from Mario's head, which rests against the underside.
+        let head = Vec2::new(event.x, event.y + 1.0);
+
+        for (brick, brick_transform, brick_collider) in brick_query.iter() {
+            if !brick_collider.contains(brick_transform.translation, head) {
+                continue;
+            }
+
+            let x = brick_transform.translation.x;
+            let y = brick_transform.translation.y;
+
+            let bump_sound = asset_server.load("stomp.ogg");
+            audio.play(bump_sound);
+            block_bump_event_writer.send(BlockBumpEvent { x, y });
+
+            if !is_big {
+                commands.entity(brick).insert(BlockBump::new(y));
+                continue;
+            }
+
+            tilemap.set_tile_at(x, y, Tile::Empty);
+            commands.entity(brick).despawn();
+            for index in 0..DEBRIS_VELOCITIES.len() {
+                commands.spawn((
+                    get_debris_bundle(&asset_server, &mut texture_atlases, index, x, y),
+                    LevelScoped,
+                ));
+            }
+            score_event_writer.send(ScoreEvent {
+                points: BRICK_POINTS,
+                x,
+                y,
+            });
+        }
+    }
+}
+
+pub fn move_debris(
+    mut commands: Commands,
+    time: Res<Time>,
+    physics: Res<PhysicsConfig>,
+    tilemap: Res<TileMap>,
+    mut debris_query: Query<(Entity, &mut Transform, &mut Velocity), With<Debris>>,
+) {
+    let delta_seconds = time.delta_seconds();
+
+    for (entity, mut transform, mut velocity) in debris_query.iter_mut() {
+        velocity.y -= physics.projectile_gravity * delta_seconds;
+        transform.translation += velocity.0.extend(0.0) * delta_seconds;
+
+        if transform.translation.y + DEBRIS_SIZE.y / 2.0 < tilemap.bottom() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
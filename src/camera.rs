@@ -0,0 +1,72 @@
+use bevy::{prelude::{Camera2d, Query, Res, Resource, Transform, With, Without}, window::Windows};
+
+use crate::{mario::Mario, physics::Velocity, tilemap::TileMap};
+
+const DEAD_ZONE_WIDTH: f32 = 24.0;
+
+/// Mario can move `dead_zone_width` either side of the camera centre before
+/// the camera starts scrolling after him. When `can_scroll_back` is off the
+/// camera only ever scrolls right and Mario can't walk off its left edge.
+#[derive(Resource)]
+pub struct CameraFollow {
+    pub dead_zone_width: f32,
+    pub can_scroll_back: bool,
+}
+
+impl Default for CameraFollow {
+    fn default() -> Self {
+        CameraFollow {
+            dead_zone_width: DEAD_ZONE_WIDTH,
+            can_scroll_back: true,
+        }
+    }
+}
+
+/// Keeps a camera of `half_width` inside the level, or centres it when the
+/// level is narrower than the window.
+pub fn clamp_camera_x(x: f32, tilemap: &TileMap, half_width: f32) -> f32 {
+    if tilemap.right() - tilemap.left() <= half_width * 2.0 {
+        (tilemap.left() + tilemap.right()) / 2.0
+    } else {
+        x.clamp(tilemap.left() + half_width, tilemap.right() - half_width)
+    }
+}
+
+pub fn follow_mario(
+    windows: Res<Windows>,
+    tilemap: Res<TileMap>,
+    camera_follow: Res<CameraFollow>,
+    mut camera_query: Query<&mut Transform, (With<Camera2d>, Without<Mario>)>,
+    mut mario_query: Query<(&mut Transform, &mut Velocity, &Mario), Without<Camera2d>>,
+) {
+    let half_width = match windows.get_primary() {
+        Some(window) => window.width() / 2.0,
+        None => return,
+    };
+
+    for (mut mario_transform, mut velocity, mario) in mario_query.iter_mut() {
+        for mut camera_transform in camera_query.iter_mut() {
+            let mario_x = mario_transform.translation.x;
+            let mut camera_x = camera_transform.translation.x;
+
+            if mario_x > camera_x + camera_follow.dead_zone_width {
+                camera_x = mario_x - camera_follow.dead_zone_width;
+            } else if mario_x < camera_x - camera_follow.dead_zone_width
+                && camera_follow.can_scroll_back
+            {
+                camera_x = mario_x + camera_follow.dead_zone_width;
+            }
+
+            camera_x = clamp_camera_x(camera_x, &tilemap, half_width);
+            camera_transform.translation.x = camera_x;
+
+            if !camera_follow.can_scroll_back {
+                let left_edge = camera_x - half_width + mario.power.size().x / 2.0;
+                if mario_transform.translation.x < left_edge {
+                    mario_transform.translation.x = left_edge;
+                    velocity.x = velocity.x.max(0.0);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use bevy::{
+    prelude::{AssetServer, Assets, Commands, Component, Entity, Query, Res, Transform, Vec2, default},
+    sprite::{SpriteSheetBundle, TextureAtlas, TextureAtlasSprite},
+    time::Time,
+};
+
+use crate::{
+    animation::{Animation, AnimationClip, AnimationMode, SpriteSheet},
+    physics::{PhysicsConfig, Velocity},
+};
+
+const BLOCK_COIN_SPEED: f32 = 300.0;
+const COIN_CLIP: AnimationClip = AnimationClip {
+    name: "spin",
+    sheet: SpriteSheet {
+        texture: "coin.png",
+        frame_size: Vec2::new(16.0, 16.0),
+        columns: 4,
+    },
+    first: 0,
+    last: 3,
+    frame_duration: 0.05,
+    mode: AnimationMode::Loop,
+};
+
+/// A coin knocked out of a question block. It spins up out of the block and
+/// disappears once it falls back down to `base_y`, the block's top.
+#[derive(Component)]
+pub struct BlockCoin {
+    base_y: f32,
+}
+
+/// `x` and `y` are the centre of the block the coin comes out of.
+pub fn get_block_coin_bundle(
+    asset_server: &AssetServer,
+    texture_atlases: &mut Assets<TextureAtlas>,
+    x: f32,
+    y: f32,
+) -> (SpriteSheetBundle, Animation, Velocity, BlockCoin) {
+    let base_y = y + COIN_CLIP.sheet.frame_size.y;
+
+    (
+        SpriteSheetBundle {
+            texture_atlas: texture_atlases.add(COIN_CLIP.sheet.texture_atlas(asset_server)),
+            sprite: TextureAtlasSprite::new(0),
+            transform: Transform::from_xyz(x, base_y, 2.5),
+            ..default()
+        },
+        Animation::new(COIN_CLIP),
+        Velocity(Vec2::new(0.0, BLOCK_COIN_SPEED)),
+        BlockCoin { base_y },
+    )
+}
+
+pub fn move_block_coins(
+    mut commands: Commands,
+    time: Res<Time>,
+    physics: Res<PhysicsConfig>,
+    mut coin_query: Query<(Entity, &mut Transform, &mut Velocity, &BlockCoin)>,
+) {
+    let delta_seconds = time.delta_seconds();
+
+    for (entity, mut transform, mut velocity, coin) in coin_query.iter_mut() {
+        velocity.y -= physics.projectile_gravity * delta_seconds;
+        transform.translation.y += velocity.y * delta_seconds;
+
+        if velocity.y < 0.0 && transform.translation.y <= coin.base_y {
+            commands.entity(entity).despawn();
+        }
+    }
+}
@@ -0,0 +1,255 @@
+use bevy::{
+    prelude::{Component, Entity, EventWriter, Or, Query, Res, Transform, Vec2, Vec3, With, Without},
+    time::Time,
+};
+
+use crate::{enemy::Enemy, fire_flower::FireFlower, fireball::Fireball, hammer::Hammer, mario::Mario, mushroom::MagicMushroom, physics::Velocity, star::Star};
+
+/// Axis-aligned hitbox centred on the entity's translation.
+#[derive(Component, Clone, Copy)]
+pub struct Collider {
+    pub size: Vec2,
+}
+
+impl Collider {
+    pub fn new(size: Vec2) -> Self {
+        Collider { size }
+    }
+
+    pub fn contains(&self, translation: Vec3, point: Vec2) -> bool {
+        let half = self.size / 2.0;
+
+        point.x >= translation.x - half.x
+            && point.x < translation.x + half.x
+            && point.y >= translation.y - half.y
+            && point.y < translation.y + half.y
+    }
+}
+
+/// Which side of the first entity of a collision touched the second one.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CollisionSide {
+    Top,
+    Bottom,
+    Left,
+    Right,
+}
+
+/// Returns the side of box `a` that overlaps box `b`, if they overlap at all.
+/// The side is taken from the axis they overlap least along, which is the
+/// axis they have only just started to overlap on.
+pub fn collide(a_translation: Vec3, a_size: Vec2, b_translation: Vec3, b_size: Vec2) -> Option<CollisionSide> {
+    let distance = b_translation.truncate() - a_translation.truncate();
+    let overlap = (a_size + b_size) / 2.0 - distance.abs();
+
+    if overlap.x <= 0.0 || overlap.y <= 0.0 {
+        return None;
+    }
+
+    if overlap.y < overlap.x {
+        if distance.y < 0.0 {
+            Some(CollisionSide::Bottom)
+        } else {
+            Some(CollisionSide::Top)
+        }
+    } else if distance.x < 0.0 {
+        Some(CollisionSide::Left)
+    } else {
+        Some(CollisionSide::Right)
+    }
+}
+
+/// Whether falling box `a` came down onto box `b` this frame: its bottom was
+/// at or above `b`'s top before both moved by their velocities. Unlike
+/// `collide`, this doesn't depend on how deep `a` has sunk into `b`, which
+/// can be deeper than the sideways overlap after a fast fall or a long frame.
+pub fn landed_on(
+    a_translation: Vec3,
+    a_size: Vec2,
+    a_velocity: Vec2,
+    b_translation: Vec3,
+    b_size: Vec2,
+    b_velocity: Vec2,
+    delta_seconds: f32,
+) -> bool {
+    let a_previous_bottom = a_translation.y - a_velocity.y * delta_seconds - a_size.y / 2.0;
+    let b_previous_top = b_translation.y - b_velocity.y * delta_seconds + b_size.y / 2.0;
+
+    a_velocity.y < 0.0 && a_previous_bottom >= b_previous_top
+}
+
+/// Whether box `a` is resting on top of box `b`, such as an enemy walking
+/// over a block.
+pub fn is_standing_on(a_translation: Vec3, a_size: Vec2, b_translation: Vec3, b_size: Vec2) -> bool {
+    let a_bottom = a_translation.y - a_size.y / 2.0;
+    let b_top = b_translation.y + b_size.y / 2.0;
+
+    (a_bottom - b_top).abs() < 1.0 && (a_translation.x - b_translation.x).abs() < (a_size.x + b_size.x) / 2.0
+}
+
+/// `side` is the side of Mario that touched the enemy, so `Bottom` is a stomp.
+pub struct MarioEnemyCollisionEvent {
+    pub enemy: Entity,
+    pub side: CollisionSide,
+}
+
+pub struct MarioItemCollisionEvent {
+    pub item: Entity,
+}
+
+pub struct FireballEnemyCollisionEvent {
+    pub fireball: Entity,
+    pub enemy: Entity,
+}
+
+/// A Hammer Bro's hammer hit Mario.
+pub struct MarioHammerCollisionEvent;
+
+/// Sent once for every pair of overlapping enemies.
+pub struct EnemyCollisionEvent {
+    pub enemy: Entity,
+    pub other: Entity,
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn detect_collisions(
+    time: Res<Time>,
+    mario_query: Query<(&Transform, &Collider, &Velocity), With<Mario>>,
+    enemy_query: Query<(Entity, &Transform, &Collider, Option<&Velocity>), With<Enemy>>,
+    item_query: Query<
+        (Entity, &Transform, &Collider),
+        (Or<(With<MagicMushroom>, With<FireFlower>, With<Star>)>, Without<Enemy>),
+    >,
+    fireball_query: Query<(Entity, &Transform, &Collider), (With<Fireball>, Without<Enemy>)>,
+    hammer_query: Query<(&Transform, &Collider), (With<Hammer>, Without<Enemy>)>,
+    mut mario_enemy_collision_event_writer: EventWriter<MarioEnemyCollisionEvent>,
+    mut mario_item_collision_event_writer: EventWriter<MarioItemCollisionEvent>,
+    mut mario_hammer_collision_event_writer: EventWriter<MarioHammerCollisionEvent>,
+    mut fireball_enemy_collision_event_writer: EventWriter<FireballEnemyCollisionEvent>,
+    mut enemy_collision_event_writer: EventWriter<EnemyCollisionEvent>,
+) {
+    let delta_seconds = time.delta_seconds();
+
+    for (mario_transform, mario_collider, mario_velocity) in mario_query.iter() {
+        for (enemy, enemy_transform, enemy_collider, enemy_velocity) in enemy_query.iter() {
+            if let Some(side) = collide(
+                mario_transform.translation,
+                mario_collider.size,
+                enemy_transform.translation,
+                enemy_collider.size,
+            ) {
+                let is_stomp = landed_on(
+                    mario_transform.translation,
+                    mario_collider.size,
+                    mario_velocity.0,
+                    enemy_transform.translation,
+                    enemy_collider.size,
+                    enemy_velocity.map_or(Vec2::ZERO, |velocity| velocity.0),
+                    delta_seconds,
+                );
+                let side = if is_stomp { CollisionSide::Bottom } else { side };
+
+                mario_enemy_collision_event_writer.send(MarioEnemyCollisionEvent { enemy, side });
+            }
+        }
+
+        for (item, item_transform, item_collider) in item_query.iter() {
+            if collide(
+                mario_transform.translation,
+                mario_collider.size,
+                item_transform.translation,
+                item_collider.size,
+            )
+            .is_some()
+            {
+                mario_item_collision_event_writer.send(MarioItemCollisionEvent { item });
+            }
+        }
+
+        for (hammer_transform, hammer_collider) in hammer_query.iter() {
+            if collide(
+                mario_transform.translation,
+                mario_collider.size,
+                hammer_transform.translation,
+                hammer_collider.size,
+            )
+            .is_some()
+            {
+                mario_hammer_collision_event_writer.send(MarioHammerCollisionEvent);
+            }
+        }
+    }
+
+    for (fireball, fireball_transform, fireball_collider) in fireball_query.iter() {
+        for (enemy, enemy_transform, enemy_collider, _) in enemy_query.iter() {
+            if collide(
+                fireball_transform.translation,
+                fireball_collider.size,
+                enemy_transform.translation,
+                enemy_collider.size,
+            )
+            .is_some()
+            {
+                fireball_enemy_collision_event_writer.send(FireballEnemyCollisionEvent { fireball, enemy });
+            }
+        }
+    }
+
+    for [(enemy, enemy_transform, enemy_collider, _), (other, other_transform, other_collider, _)] in
+        enemy_query.iter_combinations()
+    {
+        if collide(
+            enemy_transform.translation,
+            enemy_collider.size,
+            other_transform.translation,
+            other_collider.size,
+        )
+        .is_some()
+        {
+            enemy_collision_event_writer.send(EnemyCollisionEvent { enemy, other });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: Vec2 = Vec2::new(16.0, 16.0);
+
+    #[test]
+    fn deep_landing_on_an_edge_is_a_stomp() {
+        // Mario has sunk 6 units into the goomba but only overlaps it by 4
+        // sideways, so the least-overlap axis alone calls it a side hit.
+        let mario = Vec3::new(12.0, 10.0, 0.0);
+        let goomba = Vec3::ZERO;
+        let mario_velocity = Vec2::new(0.0, -270.0);
+
+        assert_eq!(collide(mario, SIZE, goomba, SIZE), Some(CollisionSide::Left));
+        assert!(landed_on(mario, SIZE, mario_velocity, goomba, SIZE, Vec2::ZERO, 1.0 / 30.0));
+    }
+
+    #[test]
+    fn walking_into_an_enemy_is_not_a_stomp() {
+        let mario = Vec3::new(14.0, 0.0, 0.0);
+
+        assert!(!landed_on(mario, SIZE, Vec2::new(-72.0, 0.0), Vec3::ZERO, SIZE, Vec2::ZERO, 1.0 / 60.0));
+    }
+
+    #[test]
+    fn falling_from_below_the_top_is_not_a_stomp() {
+        let mario = Vec3::new(14.0, 4.0, 0.0);
+
+        assert!(!landed_on(mario, SIZE, Vec2::new(0.0, -60.0), Vec3::ZERO, SIZE, Vec2::ZERO, 1.0 / 60.0));
+    }
+
+    #[test]
+    fn landing_on_a_rising_enemy_is_a_stomp() {
+        // Both moved 5 units into each other this frame.
+        let mario = Vec3::new(0.0, 11.0, 0.0);
+        let mario_velocity = Vec2::new(0.0, -300.0);
+        let enemy_velocity = Vec2::new(0.0, 300.0);
+
+        assert!(landed_on(mario, SIZE, mario_velocity, Vec3::ZERO, SIZE, enemy_velocity, 1.0 / 60.0));
+    }
+}
@@ -1,209 +1,658 @@
 
-use bevy::{prelude::{Res, Component, Query, With, Entity, Transform, EventWriter, Commands, EventReader, Visibility, AssetServer, Audio, Assets, ResMut, Resource, Handle, Vec2, default}, time::{Time, TimerMode, Timer}, sprite::{TextureAtlasSprite, TextureAtlas, SpriteSheetBundle}, audio::AudioSink};
+use bevy::{window::Windows, prelude::{Camera2d, Res, Component, Query, With, Entity, Transform, EventWriter, Commands, EventReader, Visibility, Without, AssetServer, Audio, Assets, ResMut, Resource, Handle, Vec2, State, default}, time::{Time, TimerMode, Timer}, sprite::{TextureAtlasSprite, TextureAtlas, SpriteSheetBundle}, audio::AudioSink};
 use rand::{distributions::Uniform, prelude::Distribution};
+use serde::Deserialize;
 
-use crate::{AnimationTimer, animation::{AnimationIndices, animate}, mario::{Mario, get_dead_mario_bundle, get_mario_bundle}, firework::Firework};
+use crate::{animation::{Animation, AnimationClip, AnimationMode, SpriteSheet}, behavior::{Bouncer, EnemyBehavior, Flyer, Jumper, NoTileMovement, Projectile, Walker}, mario::Mario, firework::{Firework, FIREWORK_CLIP}, physics::{PhysicsConfig, Velocity}, tilemap::{TileMap, TILE_SIZE}, game_state::{GameState, LevelScoped}, level::CurrentLevel, power::{Invulnerable, MarioPowerEvent, PowerChange}, score::{ScoreEvent, STAR_KILL_POINTS, STOMP_POINTS}, shell::{Shell, SHELL_SIZE}, star::Starman, collision::{Collider, CollisionSide, MarioEnemyCollisionEvent}};
 
-#[derive(PartialEq)]
+#[derive(Deserialize, PartialEq, Clone, Copy, Debug)]
 pub enum EnemyType {
     Goomba,
+    /// A red Koopa, so it turns around at ledges rather than walk off them.
     Turtle,
+    /// Bounces along until stomped, which knocks its wings off.
+    WingedGoomba,
+    WingedTurtle,
+    /// Pops in and out of the pipe it sits in; see `PipeDweller`.
+    PiranhaPlant,
+    /// A shelled walker that fireballs bounce off.
+    BuzzyBeetle,
+    /// Jumps between platforms throwing hammers; see `HammerBro`.
+    HammerBro,
+    /// Follows Mario overhead dropping Spinies; see `Lakitu`.
+    Lakitu,
+    Spiny,
+}
+
+impl EnemyType {
+    pub fn size(&self) -> Vec2 {
+        match self {
+            EnemyType::Goomba
+            | EnemyType::WingedGoomba
+            | EnemyType::BuzzyBeetle
+            | EnemyType::Spiny => Vec2::new(16.0, 16.0),
+            EnemyType::Turtle
+            | EnemyType::WingedTurtle
+            | EnemyType::PiranhaPlant
+            | EnemyType::HammerBro
+            | EnemyType::Lakitu => Vec2::new(16.0, 24.0),
+        }
+    }
+
+    fn walk_clip(&self) -> AnimationClip {
+        AnimationClip {
+            name: "walk",
+            sheet: SpriteSheet {
+                texture: self.texture(),
+                frame_size: self.size(),
+                columns: 2,
+            },
+            first: 0,
+            last: 1,
+            frame_duration: 0.1,
+            mode: AnimationMode::Loop,
+        }
+    }
+
+    fn texture(&self) -> &'static str {
+        match self {
+            EnemyType::Goomba => "goomba.png",
+            EnemyType::Turtle => "turtle.png",
+            EnemyType::WingedGoomba => "winged-goomba.png",
+            EnemyType::WingedTurtle => "winged-turtle.png",
+            EnemyType::PiranhaPlant => "piranha-plant.png",
+            EnemyType::BuzzyBeetle => "buzzy-beetle.png",
+            EnemyType::HammerBro => "hammer-bro.png",
+            EnemyType::Lakitu => "lakitu.png",
+            EnemyType::Spiny => "spiny.png",
+        }
+    }
+
+    /// Used unless the level gives the enemy behaviors of its own.
+    pub fn default_behaviors(&self) -> Vec<EnemyBehavior> {
+        let walker = EnemyBehavior::Walker {
+            speed: ENEMY_WALK_SPEED,
+            turn_at_ledges: false,
+            pace_distance: None,
+        };
+
+        match self {
+            EnemyType::Goomba | EnemyType::BuzzyBeetle | EnemyType::Spiny => vec![walker],
+            EnemyType::Turtle => vec![EnemyBehavior::Walker {
+                speed: ENEMY_WALK_SPEED,
+                turn_at_ledges: true,
+                pace_distance: None,
+            }],
+            EnemyType::WingedGoomba | EnemyType::WingedTurtle => {
+                vec![walker, EnemyBehavior::Bouncer { speed: ENEMY_BOUNCE_SPEED }]
+            }
+            EnemyType::PiranhaPlant => vec![EnemyBehavior::PipeDweller {
+                speed: 24.0,
+                wait: 2.0,
+            }],
+            EnemyType::HammerBro => vec![
+                EnemyBehavior::Walker {
+                    speed: 20.0,
+                    turn_at_ledges: false,
+                    pace_distance: Some(16.0),
+                },
+                EnemyBehavior::Jumper {
+                    speed: 460.0,
+                    interval: 3.0,
+                },
+                EnemyBehavior::Shooter {
+                    projectile: Projectile::Hammer,
+                    interval: 1.0,
+                    limit: 3,
+                },
+            ],
+            EnemyType::Lakitu => vec![
+                EnemyBehavior::Flyer,
+                EnemyBehavior::Chaser { speed: 80.0 },
+                EnemyBehavior::Shooter {
+                    projectile: Projectile::Spiny,
+                    interval: 3.0,
+                    limit: 3,
+                },
+            ],
+        }
+    }
+
+    /// Stomping the rest hurts Mario instead.
+    fn can_be_stomped(&self) -> bool {
+        !matches!(self, EnemyType::PiranhaPlant | EnemyType::Spiny)
+    }
+
+    /// What a winged enemy turns into once stomped.
+    fn wingless(&self) -> Option<EnemyType> {
+        match self {
+            EnemyType::WingedGoomba => Some(EnemyType::Goomba),
+            EnemyType::WingedTurtle => Some(EnemyType::Turtle),
+            _ => None,
+        }
+    }
+
+    /// Whether a stomp leaves a shell behind instead of killing it.
+    fn has_shell(&self) -> bool {
+        matches!(self, EnemyType::Turtle | EnemyType::BuzzyBeetle)
+    }
+
+    pub fn is_fireproof(&self) -> bool {
+        *self == EnemyType::BuzzyBeetle
+    }
 }
 
 pub struct EnemyDead;
 
-#[derive(Resource)]
+#[derive(Resource, Default)]
 pub struct MarioLevelMusicController(pub Handle<AudioSink>);
 
+/// How far outside the camera view an enemy wakes up and starts walking.
+const ENEMY_ACTIVATION_DISTANCE: f32 = 32.0;
+const ENEMY_WALK_SPEED: f32 = 30.0;
+const ENEMY_BOUNCE_SPEED: f32 = 300.0;
+const KNOCKED_OUT_SPEED: Vec2 = Vec2::new(60.0, 250.0);
+/// How long a stomped enemy lies flattened before it disappears.
+const SQUASHED_DURATION: f32 = 0.5;
+
 #[derive(Component)]
 pub struct Enemy {
     pub go_right: bool,
+    pub is_active: bool,
     pub enemy_type: EnemyType,
 }
 
-pub struct EnemyMoveEvent {
+pub fn get_enemy_bundle(
+    asset_server: &AssetServer,
+    texture_atlases: &mut Assets<TextureAtlas>,
+    enemy_type: EnemyType,
     x: f32,
     y: f32,
-    entity: Entity,
+) -> (SpriteSheetBundle, Animation, Collider, Enemy) {
+    let clip = enemy_type.walk_clip();
+
+    (
+        SpriteSheetBundle {
+            texture_atlas: texture_atlases.add(clip.sheet.texture_atlas(asset_server)),
+            sprite: TextureAtlasSprite::new(0),
+            transform: Transform::from_xyz(x, y, 3.0),
+            ..default()
+        },
+        Animation::new(clip),
+        Collider::new(enemy_type.size()),
+        Enemy {
+            go_right: false,
+            is_active: false,
+            enemy_type,
+        },
+    )
 }
 
-pub fn move_enemy(
-    mut enemy_query: Query<
-        (Entity, &mut Transform, &mut Enemy, &mut TextureAtlasSprite),
-        With<Enemy>,
-    >,
-    mut enemy_move_event_writer: EventWriter<EnemyMoveEvent>,
-) {
-    if enemy_query.is_empty() {
-        return;
+/// Spawns an enemy that moves with `behaviors`, or with its type's default
+/// behaviors if there are none.
+pub fn spawn_enemy(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    texture_atlases: &mut Assets<TextureAtlas>,
+    enemy_type: EnemyType,
+    behaviors: &[EnemyBehavior],
+    x: f32,
+    y: f32,
+) -> Entity {
+    let mut enemy = commands.spawn((
+        get_enemy_bundle(asset_server, texture_atlases, enemy_type, x, y),
+        Velocity::default(),
+        LevelScoped,
+    ));
+
+    let default_behaviors = enemy_type.default_behaviors();
+    let behaviors = if behaviors.is_empty() { &default_behaviors } else { behaviors };
+    for behavior in behaviors {
+        behavior.insert(&mut enemy, x, y);
     }
 
-    for (entity, mut transform, mut enemy, mut texture_atlas_sprite) in enemy_query.iter_mut() {
-        let position: f32;
+    enemy.id()
+}
 
-        if enemy.go_right {
-            if enemy.enemy_type == EnemyType::Turtle {
-                texture_atlas_sprite.flip_x = false;
-            }
-            position = transform.translation.x + 0.5;
-        } else {
-            if enemy.enemy_type == EnemyType::Turtle {
-                texture_atlas_sprite.flip_x = true;
-            }
-            position = transform.translation.x - 0.5;
+/// An enemy killed by anything but a stomp: a bump from below, a sliding
+/// shell, a fireball or an invincible Mario. It is out of play, so it no
+/// longer has `Enemy`; it turns upside down on the frame it was on, hops away
+/// and falls out of the level.
+#[derive(Component)]
+pub struct KnockedOut;
+
+pub fn knock_out_enemy(commands: &mut Commands, enemy: Entity, go_right: bool) {
+    let direction_x = if go_right { 1.0 } else { -1.0 };
+
+    commands
+        .entity(enemy)
+        .remove::<(Enemy, Collider, Shell, Animation)>()
+        .insert((
+            KnockedOut,
+            Velocity(Vec2::new(direction_x * KNOCKED_OUT_SPEED.x, KNOCKED_OUT_SPEED.y)),
+        ));
+}
+
+/// A stomped enemy, lying flattened where it died until `timer` runs out. It
+/// is out of play like a `KnockedOut` enemy.
+#[derive(Component)]
+pub struct Squashed {
+    timer: Timer,
+}
+
+pub fn squash_enemy(commands: &mut Commands, enemy: Entity, transform: &mut Transform, enemy_type: EnemyType) {
+    // Flatten the sprite to half height, keeping its feet on the ground.
+    transform.scale.y = 0.5;
+    transform.translation.y -= enemy_type.size().y / 4.0;
+
+    commands
+        .entity(enemy)
+        .remove::<(Enemy, Collider, Shell, Animation)>()
+        .insert(Squashed {
+            timer: Timer::from_seconds(SQUASHED_DURATION, TimerMode::Once),
+        });
+}
+
+pub fn tick_squashed_enemies(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut Squashed)>,
+) {
+    for (entity, mut squashed) in query.iter_mut() {
+        if squashed.timer.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
         }
+    }
+}
 
-        transform.translation.x = position.clamp(-250.0, 180.0);
-        if transform.translation.x == -250.0 {
-            enemy.go_right = true;
-        } else if transform.translation.x == 180.0 {
-            enemy.go_right = false;
+pub fn move_knocked_out_enemies(
+    mut commands: Commands,
+    time: Res<Time>,
+    physics: Res<PhysicsConfig>,
+    tilemap: Res<TileMap>,
+    mut query: Query<(Entity, &mut Transform, &mut TextureAtlasSprite, &mut Velocity), With<KnockedOut>>,
+) {
+    let delta_seconds = time.delta_seconds();
+
+    for (entity, mut transform, mut sprite, mut velocity) in query.iter_mut() {
+        sprite.flip_y = true;
+        velocity.y -= physics.projectile_gravity * delta_seconds;
+        transform.translation += velocity.0.extend(0.0) * delta_seconds;
+
+        // No enemy is taller than two tiles, so by now it is out of sight.
+        if transform.translation.y + TILE_SIZE < tilemap.bottom() {
+            commands.entity(entity).despawn();
         }
+    }
+}
 
-        enemy_move_event_writer.send(EnemyMoveEvent {
-            entity,
-            x: transform.translation.x,
-            y: transform.translation.y,
-        });
+pub fn activate_enemies(
+    windows: Res<Windows>,
+    camera_query: Query<&Transform, With<Camera2d>>,
+    mut enemy_query: Query<(&Transform, &mut Enemy)>,
+) {
+    let half_width = match windows.get_primary() {
+        Some(window) => window.width() / 2.0,
+        None => return,
+    };
+
+    for camera_transform in camera_query.iter() {
+        for (transform, mut enemy) in enemy_query.iter_mut() {
+            if !enemy.is_active
+                && (transform.translation.x - camera_transform.translation.x).abs()
+                    <= half_width + ENEMY_ACTIVATION_DISTANCE
+            {
+                enemy.is_active = true;
+            }
+        }
     }
 }
 
-pub fn animate_enemies(
+/// Moves every enemy by its velocity under gravity, stopping it at tiles and
+/// turning it around at walls. Its behaviors decide the velocity.
+#[allow(clippy::type_complexity)]
+pub fn move_enemy(
+    mut commands: Commands,
     time: Res<Time>,
-    mut query: Query<
+    physics: Res<PhysicsConfig>,
+    tilemap: Res<TileMap>,
+    mut enemy_query: Query<
         (
-            &mut AnimationTimer,
-            &mut TextureAtlasSprite,
-            &AnimationIndices,
+            Entity,
+            &mut Transform,
+            &mut Velocity,
+            &mut Enemy,
+            Option<&Walker>,
+            Option<&Jumper>,
+            Option<&Flyer>,
         ),
-        With<Enemy>,
+        (Without<Shell>, Without<NoTileMovement>),
     >,
+    mut enemy_dead_event_writer: EventWriter<EnemyDead>,
 ) {
-    for (mut timer, mut sprite, animation_indices) in query.iter_mut() {
-        animate(&time, &mut timer, &mut sprite, animation_indices);
+    let delta_seconds = time.delta_seconds();
+
+    for (entity, mut transform, mut velocity, mut enemy, walker, jumper, flyer) in enemy_query.iter_mut() {
+        if !enemy.is_active {
+            continue;
+        }
+
+        let size = enemy.enemy_type.size();
+        let half = size / 2.0;
+
+        if flyer.is_none() {
+            velocity.y = (velocity.y - physics.gravity * delta_seconds).max(-physics.max_fall_speed);
+        }
+
+        let start = transform.translation;
+        let collision = tilemap.move_and_collide(
+            &mut transform.translation,
+            size,
+            Vec2::new(velocity.x * delta_seconds, 0.0),
+        );
+
+        if collision.left {
+            enemy.go_right = true;
+        } else if collision.right {
+            enemy.go_right = false;
+        }
+
+        // Ledge-aware walkers step back and turn as soon as their leading foot
+        // would leave the ground.
+        let turns_at_ledges = walker.is_some_and(|walker| walker.turn_at_ledges);
+        if turns_at_ledges && velocity.x != 0.0 {
+            let feet_row = tilemap.row_at(start.y - half.y - 1.0);
+            let lead_x = transform.translation.x + velocity.x.signum() * (half.x - 0.01);
+            let was_grounded = tilemap.is_solid(tilemap.column_at(start.x), feet_row);
+            if was_grounded && !tilemap.is_solid(tilemap.column_at(lead_x), feet_row) {
+                transform.translation.x = start.x;
+                enemy.go_right = velocity.x < 0.0;
+            }
+        }
+
+        if jumper.is_some_and(|jumper| jumper.is_passing_through(&velocity)) {
+            transform.translation.y += velocity.y * delta_seconds;
+        } else {
+            let collision = tilemap.move_and_collide(
+                &mut transform.translation,
+                size,
+                Vec2::new(0.0, velocity.y * delta_seconds),
+            );
+
+            if collision.bottom || collision.top {
+                velocity.y = 0.0;
+            }
+        }
+
+        if transform.translation.y + half.y < tilemap.bottom() {
+            commands.entity(entity).despawn();
+            enemy_dead_event_writer.send(EnemyDead);
+        }
     }
 }
 
-
-pub fn enemy_move_event_read_for_mario(
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn mario_enemy_collision_event_read(
     mut commands: Commands,
-    enemy_move_event_reader: EventReader<EnemyMoveEvent>,
+    mut mario_enemy_collision_event_reader: EventReader<MarioEnemyCollisionEvent>,
     mut enemy_dead_event_writer: EventWriter<EnemyDead>,
-    mut mario_query: Query<(&Visibility, &Transform, &mut Mario, Entity), With<Mario>>,
+    mut mario_power_event_writer: EventWriter<MarioPowerEvent>,
+    mut score_event_writer: EventWriter<ScoreEvent>,
+    mario_query: Query<(&Transform, Option<&Starman>, Option<&Invulnerable>), With<Mario>>,
+    mut enemy_query: Query<
+        (&mut Transform, &mut TextureAtlasSprite, &mut Animation, &mut Enemy, Option<(&mut Shell, &mut Velocity)>),
+        Without<Mario>,
+    >,
     asset_server: Res<AssetServer>,
     audio: Res<Audio>,
-    audio_sinks: Res<Assets<AudioSink>>,
-    music_controller: Res<MarioLevelMusicController>,
-    texture_atlases: ResMut<Assets<TextureAtlas>>,
 ) {
-    if mario_query.is_empty() {
-        return;
-    }
-
-    let mario = &mut mario_query.single_mut();
+    let (mario_transform, starman, invulnerable) = match mario_query.get_single() {
+        Ok(mario) => mario,
+        Err(_) => {
+            mario_enemy_collision_event_reader.clear();
+            return;
+        }
+    };
 
-    let enemy_kill_y_limit = if mario.2.is_super_mario { -70.0 } else { -74.0 };
+    for event in mario_enemy_collision_event_reader.iter() {
+        let (mut transform, mut sprite, mut animation, mut enemy, shell) = match enemy_query.get_mut(event.enemy) {
+            Ok(enemy) => enemy,
+            Err(_) => continue,
+        };
+        let is_stomp = event.side == CollisionSide::Bottom && enemy.enemy_type.can_be_stomped();
+        let x = transform.translation.x;
+        let y = transform.translation.y;
 
-    if let Some(entity) = is_enemy_hit_mario(enemy_move_event_reader, mario.1) {
-        if mario.1.translation.y > enemy_kill_y_limit {
+        if starman.is_some() {
             let enemy_kill_sound = asset_server.load("stomp.ogg");
             audio.play(enemy_kill_sound);
-            commands.entity(entity).despawn();
+            knock_out_enemy(&mut commands, event.enemy, x >= mario_transform.translation.x);
             enemy_dead_event_writer.send(EnemyDead);
-        } else if !mario.2.is_super_mario {
-            if let Some(sink) = audio_sinks.get(&music_controller.0) {
-                sink.stop();
+            score_event_writer.send(ScoreEvent {
+                points: STAR_KILL_POINTS,
+                x,
+                y,
+            });
+            continue;
+        }
+
+        if let Some((mut shell, mut velocity)) = shell {
+            if !shell.grace.finished() {
+                continue;
+            }
+
+            if !Shell::is_sliding(&velocity) {
+                let kick_sound = asset_server.load("stomp.ogg");
+                audio.play(kick_sound);
+                shell.kick(&mut velocity, x >= mario_transform.translation.x);
+                continue;
             }
 
-            let mariodie = asset_server.load("mariodie.ogg");
-            audio.play(mariodie);
-            commands.entity(mario.3).despawn();
-            commands.spawn(get_dead_mario_bundle(
-                asset_server,
-                texture_atlases,
-                mario.1.translation.x,
-                mario.1.translation.y,
-            ));
-        } else if mario.2.is_super_mario {
-            let mariodie = asset_server.load("powerdown.ogg");
-            audio.play(mariodie);
-            commands.entity(mario.3).despawn();
-            commands.spawn(get_mario_bundle(
-                asset_server,
-                texture_atlases,
-                false,
-                mario.1.translation.x + 50.0,
-                -74.0,
-            ));
+            if is_stomp {
+                let enemy_kill_sound = asset_server.load("stomp.ogg");
+                audio.play(enemy_kill_sound);
+                shell.stop(&mut velocity);
+                continue;
+            }
+        } else if let (true, Some(wingless)) = (is_stomp, enemy.enemy_type.wingless()) {
+            let enemy_kill_sound = asset_server.load("stomp.ogg");
+            audio.play(enemy_kill_sound);
+            enemy.enemy_type = wingless;
+            animation.play(wingless.walk_clip());
+            commands.entity(event.enemy).remove::<Bouncer>();
+            score_event_writer.send(ScoreEvent {
+                points: STOMP_POINTS,
+                x,
+                y,
+            });
+            continue;
+        } else if is_stomp && enemy.enemy_type.has_shell() {
+            let enemy_kill_sound = asset_server.load("stomp.ogg");
+            audio.play(enemy_kill_sound);
+            transform.translation.y -= (enemy.enemy_type.size().y - SHELL_SIZE.y) / 2.0;
+            sprite.index = 0;
+            sprite.flip_y = true;
+            commands
+                .entity(event.enemy)
+                .remove::<Animation>()
+                .insert((Shell::default(), Velocity::default(), Collider::new(SHELL_SIZE)));
+            enemy_dead_event_writer.send(EnemyDead);
+            score_event_writer.send(ScoreEvent {
+                points: STOMP_POINTS,
+                x,
+                y,
+            });
+            continue;
+        }
+
+        if is_stomp {
+            let enemy_kill_sound = asset_server.load("stomp.ogg");
+            audio.play(enemy_kill_sound);
+            squash_enemy(&mut commands, event.enemy, &mut transform, enemy.enemy_type);
+            enemy_dead_event_writer.send(EnemyDead);
+            score_event_writer.send(ScoreEvent {
+                points: STOMP_POINTS,
+                x,
+                y,
+            });
+        } else if invulnerable.is_none() {
+            mario_power_event_writer.send(MarioPowerEvent {
+                change: PowerChange::Damage,
+            });
+            return;
         }
     }
 }
 
-fn is_enemy_hit_mario(
-    mut enemy_move_event_reader: EventReader<EnemyMoveEvent>,
-    transform: &Transform,
-) -> Option<Entity> {
-    let mut hit_event: Option<Entity> = None;
-
-    for event in enemy_move_event_reader.iter() {
-        if event.x >= transform.translation.x - 5.0
-            && event.x <= transform.translation.x + 5.0
-            && (event.y >= transform.translation.y - 8.0
-                && event.y <= transform.translation.y + 8.0)
-        {
-            hit_event = Some(event.entity);
-        }
+/// Clears the stage once an enemy has died and no enemy is left. The death is
+/// latched into `CurrentLevel`, since the last enemy is only despawned at the
+/// end of the frame and its `EnemyDead` events would expire if another state,
+/// such as `Transforming` or `Paused`, took over before the next check.
+pub fn clear_stage_when_enemies_gone(
+    enemy_dead_event_reader: EventReader<EnemyDead>,
+    enemy_query: Query<(), (With<Enemy>, Without<Shell>)>,
+    mut current_level: ResMut<CurrentLevel>,
+    mut game_state: ResMut<State<GameState>>,
+) {
+    if !enemy_dead_event_reader.is_empty() {
+        enemy_dead_event_reader.clear();
+        current_level.has_enemy_died = true;
     }
 
-    enemy_move_event_reader.clear();
+    if !current_level.has_enemy_died || !enemy_query.is_empty() {
+        return;
+    }
 
-    return hit_event;
+    if game_state.set(GameState::StageClear).is_ok() {
+        current_level.has_enemy_died = false;
+    }
 }
 
-pub fn enemy_dead_event_read(
+/// Stops the level music and sets up the fireworks for the stage clear.
+pub fn start_stage_clear(
     mut commands: Commands,
-    enemy_query: Query<Entity, With<Enemy>>,
-    enemy_dead_event_read: EventReader<EnemyDead>,
+    camera_query: Query<&Transform, With<Camera2d>>,
     asset_server: Res<AssetServer>,
     audio: Res<Audio>,
     audio_sinks: Res<Assets<AudioSink>>,
     music_controller: Res<MarioLevelMusicController>,
     mut texture_atlases: ResMut<Assets<TextureAtlas>>,
 ) {
-    if enemy_dead_event_read.is_empty() || !enemy_query.is_empty() {
-        return;
-    }
-
     if let Some(sink) = audio_sinks.get(&music_controller.0) {
         sink.stop();
     }
 
-    let mariodie = asset_server.load("stage_clear.ogg");
-    audio.play(mariodie);
+    let stage_clear_music = asset_server.load("stage_clear.ogg");
+    audio.play(stage_clear_music);
+
+    let camera_x = camera_query
+        .get_single()
+        .map(|camera_transform| camera_transform.translation.x)
+        .unwrap_or(0.0);
 
     let mut rng = rand::thread_rng();
-    let x_range = Uniform::from(-250.0..180.0);
+    let x_range = Uniform::from(camera_x - 250.0..camera_x + 180.0);
     let y_range = Uniform::from(10.0..110.0);
 
     for _ in 1..8 {
         let x = x_range.sample(&mut rng);
         let y = y_range.sample(&mut rng);
 
-        let texture_handle = asset_server.load("firework.png");
-        let texture_atlas =
-            TextureAtlas::from_grid(texture_handle, Vec2::new(16.0, 16.0), 16, 1, None, None);
-
         commands.spawn((
             SpriteSheetBundle {
                 visibility: Visibility { is_visible: false },
-                texture_atlas: texture_atlases.add(texture_atlas),
+                texture_atlas: texture_atlases.add(FIREWORK_CLIP.sheet.texture_atlas(&asset_server)),
                 sprite: TextureAtlasSprite::new(0),
                 transform: Transform::from_xyz(x, y, 2.0),
                 ..default()
             },
-            AnimationIndices { first: 0, last: 15 },
-            AnimationTimer(Timer::from_seconds(0.1, TimerMode::Repeating)),
             Firework,
+            LevelScoped,
         ));
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::{App, SystemSet};
+
+    use super::*;
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_state(GameState::Playing)
+            .add_event::<EnemyDead>()
+            .init_resource::<CurrentLevel>()
+            .add_system_set(SystemSet::on_update(GameState::Playing).with_system(clear_stage_when_enemies_gone));
+        app
+    }
+
+    fn spawn_goomba(app: &mut App) -> Entity {
+        app.world
+            .spawn(Enemy {
+                go_right: false,
+                is_active: true,
+                enemy_type: EnemyType::Goomba,
+            })
+            .id()
+    }
+
+    fn game_state(app: &App) -> GameState {
+        *app.world.resource::<State<GameState>>().current()
+    }
+
+    #[test]
+    fn clears_stage_once_last_enemy_is_gone() {
+        let mut app = app();
+        let goomba = spawn_goomba(&mut app);
+        app.update();
+
+        app.world.send_event(EnemyDead);
+        app.update();
+        assert_eq!(game_state(&app), GameState::Playing);
+
+        app.world.despawn(goomba);
+        app.update();
+        assert_eq!(game_state(&app), GameState::StageClear);
+    }
+
+    #[test]
+    fn clears_stage_after_power_change_on_the_last_kill() {
+        let mut app = app();
+        let goomba = spawn_goomba(&mut app);
+        app.update();
+
+        // The last kill also powers Mario up, so `Transforming` takes over
+        // from the next frame, for longer than its `EnemyDead` event lasts.
+        app.world.send_event(EnemyDead);
+        app.update();
+        app.world.despawn(goomba);
+        app.world
+            .resource_mut::<State<GameState>>()
+            .push(GameState::Transforming)
+            .unwrap();
+        for _ in 0..5 {
+            app.update();
+        }
+        assert_eq!(game_state(&app), GameState::Transforming);
+
+        app.world.resource_mut::<State<GameState>>().pop().unwrap();
+        app.update();
+        app.update();
+        assert_eq!(game_state(&app), GameState::StageClear);
+    }
+
+    #[test]
+    fn does_not_clear_stage_while_enemies_are_left() {
+        let mut app = app();
+        spawn_goomba(&mut app);
+        let goomba = spawn_goomba(&mut app);
+        app.update();
+
+        app.world.send_event(EnemyDead);
+        app.world.despawn(goomba);
+        app.update();
+        app.update();
+        assert_eq!(game_state(&app), GameState::Playing);
+    }
+}
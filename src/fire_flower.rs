@@ -0,0 +1,77 @@
+use bevy::{prelude::{Query, Transform, With, Component, AssetServer, Assets, Vec2, Res, ResMut, Commands, EventReader, default}, sprite::{TextureAtlas, SpriteSheetBundle}, time::Time};
+
+use crate::{collision::Collider, game_state::LevelScoped, physics::PhysicsConfig, tilemap::TileMap};
+
+const FIRE_FLOWER_SIZE: Vec2 = Vec2::new(16.0, 16.0);
+
+/// Comes out of a mushroom block instead of the mushroom when Mario is
+/// already super. Unlike the mushroom it never moves once it is out.
+#[derive(Component)]
+pub struct FireFlower {
+    pub is_released: bool,
+}
+
+/// Sent by blocks that always hold a fire flower, whatever Mario's power.
+/// `x` and `y` are the centre of the question block it comes out of.
+pub struct FireFlowerReleaseEvent {
+    pub x: f32,
+    pub y: f32,
+}
+
+pub fn get_fire_flower_bundle(
+    asset_server: &AssetServer,
+    texture_atlases: &mut Assets<TextureAtlas>,
+    x: f32,
+    y: f32,
+) -> (SpriteSheetBundle, Collider, FireFlower) {
+    let fire_flower_texture_atlas = TextureAtlas::from_grid(
+        asset_server.load("fire-flower.png"),
+        FIRE_FLOWER_SIZE,
+        1,
+        1,
+        None,
+        None,
+    );
+
+    (
+        SpriteSheetBundle {
+            texture_atlas: texture_atlases.add(fire_flower_texture_atlas),
+            transform: Transform::from_xyz(x, y, 2.5),
+            ..default()
+        },
+        Collider::new(FIRE_FLOWER_SIZE),
+        FireFlower { is_released: false },
+    )
+}
+
+pub fn fire_flower_release_event_read(
+    mut commands: Commands,
+    mut fire_flower_release_event_reader: EventReader<FireFlowerReleaseEvent>,
+    asset_server: Res<AssetServer>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+) {
+    for event in fire_flower_release_event_reader.iter() {
+        commands.spawn((
+            get_fire_flower_bundle(&asset_server, &mut texture_atlases, event.x, event.y),
+            LevelScoped,
+        ));
+    }
+}
+
+pub fn grow_fire_flowers(
+    time: Res<Time>,
+    physics: Res<PhysicsConfig>,
+    tilemap: Res<TileMap>,
+    mut query: Query<(&mut Transform, &mut FireFlower), With<FireFlower>>,
+) {
+    let delta_seconds = time.delta_seconds();
+
+    for (mut transform, mut fire_flower) in query.iter_mut() {
+        if !fire_flower.is_released {
+            transform.translation.y += physics.item_rise_speed * delta_seconds;
+            if !tilemap.overlaps_solid(transform.translation, FIRE_FLOWER_SIZE) {
+                fire_flower.is_released = true;
+            }
+        }
+    }
+}
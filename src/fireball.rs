@@ -0,0 +1,168 @@
+use bevy::{
+    prelude::{
+        AssetServer, Assets, Audio, Commands, Component, Entity, EventReader, EventWriter, Input,
+        KeyCode, Query, Res, ResMut, Transform, Vec2, With, default,
+    },
+    sprite::{SpriteSheetBundle, TextureAtlas, TextureAtlasSprite},
+    time::Time,
+};
+
+use crate::{
+    collision::{Collider, FireballEnemyCollisionEvent},
+    enemy::{knock_out_enemy, Enemy, EnemyDead},
+    game_state::LevelScoped,
+    mario::Mario,
+    physics::{PhysicsConfig, Velocity},
+    power::MarioPowerState,
+    score::{ScoreEvent, FIREBALL_POINTS},
+    tilemap::TileMap,
+};
+
+const FIREBALL_SIZE: Vec2 = Vec2::new(8.0, 8.0);
+const FIREBALL_SPEED: f32 = 180.0;
+const FIREBALL_BOUNCE_SPEED: f32 = 120.0;
+const MAX_FIREBALLS: usize = 2;
+
+#[derive(Component)]
+pub struct Fireball;
+
+pub fn get_fireball_bundle(
+    asset_server: &AssetServer,
+    texture_atlases: &mut Assets<TextureAtlas>,
+    go_right: bool,
+    x: f32,
+    y: f32,
+) -> (SpriteSheetBundle, Velocity, Collider, Fireball) {
+    let fireball_texture_atlas = TextureAtlas::from_grid(
+        asset_server.load("fireball.png"),
+        FIREBALL_SIZE,
+        1,
+        1,
+        None,
+        None,
+    );
+
+    (
+        SpriteSheetBundle {
+            texture_atlas: texture_atlases.add(fireball_texture_atlas),
+            sprite: TextureAtlasSprite::new(0),
+            transform: Transform::from_xyz(x, y, 3.0),
+            ..default()
+        },
+        Velocity(Vec2::new(if go_right { FIREBALL_SPEED } else { -FIREBALL_SPEED }, 0.0)),
+        Collider::new(FIREBALL_SIZE),
+        Fireball,
+    )
+}
+
+/// Fire Mario throws a fireball the way he is facing each time Z is pressed,
+/// with at most `MAX_FIREBALLS` in the air at once.
+pub fn throw_fireballs(
+    mut commands: Commands,
+    keyboard_input: Res<Input<KeyCode>>,
+    mario_query: Query<(&Transform, &TextureAtlasSprite, &Mario)>,
+    fireball_query: Query<Entity, With<Fireball>>,
+    asset_server: Res<AssetServer>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::Z) || fireball_query.iter().count() >= MAX_FIREBALLS {
+        return;
+    }
+
+    for (transform, sprite, mario) in mario_query.iter() {
+        if mario.power != MarioPowerState::Fire {
+            continue;
+        }
+
+        let go_right = !sprite.flip_x;
+        let direction_x = if go_right { 1.0 } else { -1.0 };
+
+        commands.spawn((
+            get_fireball_bundle(
+                &asset_server,
+                &mut texture_atlases,
+                go_right,
+                transform.translation.x + direction_x * 8.0,
+                transform.translation.y + 4.0,
+            ),
+            LevelScoped,
+        ));
+    }
+}
+
+pub fn move_fireballs(
+    mut commands: Commands,
+    time: Res<Time>,
+    physics: Res<PhysicsConfig>,
+    tilemap: Res<TileMap>,
+    mut fireball_query: Query<(Entity, &mut Transform, &mut Velocity), With<Fireball>>,
+) {
+    let delta_seconds = time.delta_seconds();
+
+    for (entity, mut transform, mut velocity) in fireball_query.iter_mut() {
+        velocity.y -= physics.projectile_gravity * delta_seconds;
+
+        let collision = tilemap.move_and_collide(
+            &mut transform.translation,
+            FIREBALL_SIZE,
+            velocity.0 * delta_seconds,
+        );
+
+        if collision.bottom {
+            velocity.y = FIREBALL_BOUNCE_SPEED;
+        } else if collision.top {
+            velocity.y = 0.0;
+        }
+
+        if collision.left
+            || collision.right
+            || transform.translation.y + FIREBALL_SIZE.y / 2.0 < tilemap.bottom()
+        {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn fireball_enemy_collision_event_read(
+    mut commands: Commands,
+    mut fireball_enemy_collision_event_reader: EventReader<FireballEnemyCollisionEvent>,
+    fireball_query: Query<&Velocity, With<Fireball>>,
+    enemy_query: Query<(&Transform, &Enemy)>,
+    mut enemy_dead_event_writer: EventWriter<EnemyDead>,
+    mut score_event_writer: EventWriter<ScoreEvent>,
+    asset_server: Res<AssetServer>,
+    audio: Res<Audio>,
+) {
+    let mut hit_entities: Vec<Entity> = Vec::new();
+
+    for event in fireball_enemy_collision_event_reader.iter() {
+        if hit_entities.contains(&event.fireball) || hit_entities.contains(&event.enemy) {
+            continue;
+        }
+
+        if let Ok((transform, enemy)) = enemy_query.get(event.enemy) {
+            hit_entities.push(event.fireball);
+            hit_entities.push(event.enemy);
+
+            if enemy.enemy_type.is_fireproof() {
+                commands.entity(event.fireball).despawn();
+                continue;
+            }
+
+            let enemy_kill_sound = asset_server.load("stomp.ogg");
+            audio.play(enemy_kill_sound);
+            let go_right = fireball_query
+                .get(event.fireball)
+                .map_or(true, |velocity| velocity.x > 0.0);
+            commands.entity(event.fireball).despawn();
+            knock_out_enemy(&mut commands, event.enemy, go_right);
+            enemy_dead_event_writer.send(EnemyDead);
+            score_event_writer.send(ScoreEvent {
+                points: FIREBALL_POINTS,
+                x: transform.translation.x,
+                y: transform.translation.y,
+            });
+        }
+    }
+}
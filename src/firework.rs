@@ -1,49 +1,51 @@
-use bevy::{prelude::{Commands, Res, Query, Visibility, Entity, With, Component, AssetServer, Audio}, time::Time, sprite::TextureAtlasSprite};
+use bevy::prelude::{Commands, Res, Query, Visibility, Entity, With, Component, AssetServer, Audio, EventReader, Vec2};
 
-use crate::{AnimationTimer, animation::{AnimationIndices, animate}};
+use crate::animation::{Animation, AnimationClip, AnimationFinishedEvent, AnimationMode, SpriteSheet};
 
+pub const FIREWORK_CLIP: AnimationClip = AnimationClip {
+    name: "burst",
+    sheet: SpriteSheet {
+        texture: "firework.png",
+        frame_size: Vec2::new(16.0, 16.0),
+        columns: 16,
+    },
+    first: 0,
+    last: 15,
+    frame_duration: 0.1,
+    mode: AnimationMode::Once,
+};
+
+/// Hidden until its turn comes, then bursts once and disappears.
 #[derive(Component)]
 pub struct Firework;
 
 pub fn show_fireworks(
-    mut query: Query<(&mut AnimationTimer, &mut Visibility), With<Firework>>,
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut Visibility), With<Firework>>,
     asset_server: Res<AssetServer>,
     audio: Res<Audio>,
 ) {
-    if query.is_empty() {
+    // One at a time: the next waits until the last has burst.
+    if query.iter().any(|(_, visibility)| visibility.is_visible) {
         return;
     }
 
-    if let Some(mut firework) = query.iter_mut().next() {
-        if !firework.1.is_visible {
-            firework.1.is_visible = true;
-            let firework_sound = asset_server.load("firework.ogg");
-            audio.play(firework_sound);
-        }
+    if let Some((entity, mut visibility)) = query.iter_mut().next() {
+        visibility.is_visible = true;
+        commands.entity(entity).insert(Animation::new(FIREWORK_CLIP));
+        let firework_sound = asset_server.load("firework.ogg");
+        audio.play(firework_sound);
     }
 }
 
-pub fn animate_fireworks(
+pub fn despawn_fireworks(
     mut commands: Commands,
-    time: Res<Time>,
-    mut query: Query<
-        (
-            &mut AnimationTimer,
-            &mut TextureAtlasSprite,
-            &AnimationIndices,
-            &Visibility,
-            Entity,
-        ),
-        With<Firework>,
-    >,
+    mut animation_finished_event_reader: EventReader<AnimationFinishedEvent>,
+    query: Query<(), With<Firework>>,
 ) {
-    for (mut timer, mut sprite, animation_indices, visibility, entity) in query.iter_mut() {
-        if visibility.is_visible {
-            animate(&time, &mut timer, &mut sprite, animation_indices);
-
-            if sprite.index == 15 {
-                commands.entity(entity).despawn();
-            }
+    for event in animation_finished_event_reader.iter() {
+        if event.clip == FIREWORK_CLIP.name && query.contains(event.entity) {
+            commands.entity(event.entity).despawn();
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,237 @@
+use bevy::{
+    audio::AudioSink,
+    prelude::{
+        AlignItems, AssetServer, Assets, BackgroundColor, BuildChildren, Color, Commands, Component,
+        Deref, DerefMut, DespawnRecursiveExt, Entity, FlexDirection, Input, JustifyContent, KeyCode, NodeBundle, Query,
+        Res, ResMut, Resource, Size, State, Style, TextBundle, TextStyle, Val, With, default,
+    },
+};
+
+use crate::{
+    enemy::MarioLevelMusicController,
+    level::{CurrentLevel, LEVELS},
+    score::Score,
+    star::StarMusicController,
+};
+
+pub const FONT: &str = "fonts/DejaVuSansMono-Bold.ttf";
+
+const STARTING_LIVES: u32 = 3;
+
+/// Lives left, counting the one being played.
+#[derive(Resource, Deref, DerefMut)]
+pub struct Lives(pub u32);
+
+impl Default for Lives {
+    fn default() -> Self {
+        Lives(STARTING_LIVES)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GameState {
+    Title,
+    Playing,
+    Paused,
+    /// Mario is growing or shrinking and everything else is frozen.
+    Transforming,
+    GameOver,
+    StageClear,
+}
+
+/// Marks everything that belongs to the level being played, so it can all be
+/// despawned once the level is over.
+#[derive(Component)]
+pub struct LevelScoped;
+
+#[derive(Component)]
+pub struct TitleScreen;
+
+#[derive(Component)]
+pub struct PauseScreen;
+
+#[derive(Component)]
+pub struct GameOverScreen;
+
+#[derive(Component)]
+pub struct StageClearScreen;
+
+pub fn despawn_with<T: Component>(mut commands: Commands, query: Query<Entity, With<T>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn spawn_screen<T: Component>(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    screen: T,
+    background_color: Color,
+    title: &str,
+    subtitle: &str,
+) {
+    let font = asset_server.load(FONT);
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                background_color: BackgroundColor(background_color),
+                ..default()
+            },
+            screen,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                title,
+                TextStyle {
+                    font: font.clone(),
+                    font_size: 24.0,
+                    color: Color::WHITE,
+                },
+            ));
+            parent.spawn(TextBundle::from_section(
+                subtitle,
+                TextStyle {
+                    font,
+                    font_size: 12.0,
+                    color: Color::WHITE,
+                },
+            ));
+        });
+}
+
+pub fn spawn_title_screen(mut commands: Commands, asset_server: Res<AssetServer>) {
+    spawn_screen(
+        &mut commands,
+        &asset_server,
+        TitleScreen,
+        Color::rgb(0.36, 0.58, 0.99),
+        "SUPER MARIO RUST",
+        "PRESS ENTER TO START",
+    );
+}
+
+pub fn spawn_pause_screen(mut commands: Commands, asset_server: Res<AssetServer>) {
+    spawn_screen(
+        &mut commands,
+        &asset_server,
+        PauseScreen,
+        Color::rgba(0.0, 0.0, 0.0, 0.5),
+        "PAUSED",
+        "PRESS ESCAPE TO RESUME",
+    );
+}
+
+pub fn spawn_game_over_screen(mut commands: Commands, asset_server: Res<AssetServer>) {
+    spawn_screen(
+        &mut commands,
+        &asset_server,
+        GameOverScreen,
+        Color::rgba(0.0, 0.0, 0.0, 0.8),
+        "GAME OVER",
+        "PRESS ENTER",
+    );
+}
+
+pub fn spawn_stage_clear_screen(mut commands: Commands, asset_server: Res<AssetServer>) {
+    spawn_screen(
+        &mut commands,
+        &asset_server,
+        StageClearScreen,
+        Color::rgba(0.0, 0.0, 0.0, 0.3),
+        "STAGE CLEAR",
+        "PRESS ENTER",
+    );
+}
+
+pub fn start_game(
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut game_state: ResMut<State<GameState>>,
+    mut current_level: ResMut<CurrentLevel>,
+    mut lives: ResMut<Lives>,
+    mut score: ResMut<Score>,
+) {
+    if keyboard_input.clear_just_pressed(KeyCode::Return) {
+        current_level.go_to(0);
+        *lives = Lives::default();
+        *score = Score::default();
+        let _ = game_state.set(GameState::Playing);
+    }
+}
+
+pub fn pause_game(
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut game_state: ResMut<State<GameState>>,
+) {
+    if keyboard_input.clear_just_pressed(KeyCode::Escape) {
+        let _ = game_state.push(GameState::Paused);
+    }
+}
+
+pub fn resume_game(
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut game_state: ResMut<State<GameState>>,
+) {
+    if keyboard_input.clear_just_pressed(KeyCode::Escape) {
+        let _ = game_state.pop();
+    }
+}
+
+pub fn pause_music(
+    audio_sinks: Res<Assets<AudioSink>>,
+    music_controller: Res<MarioLevelMusicController>,
+    star_music_controller: Res<StarMusicController>,
+) {
+    for handle in [&music_controller.0, &star_music_controller.0] {
+        if let Some(sink) = audio_sinks.get(handle) {
+            sink.pause();
+        }
+    }
+}
+
+/// Resumes the star theme if Mario is invincible, otherwise the level music.
+pub fn resume_music(
+    audio_sinks: Res<Assets<AudioSink>>,
+    music_controller: Res<MarioLevelMusicController>,
+    star_music_controller: Res<StarMusicController>,
+) {
+    let sink = audio_sinks
+        .get(&star_music_controller.0)
+        .or_else(|| audio_sinks.get(&music_controller.0));
+
+    if let Some(sink) = sink {
+        sink.play();
+    }
+}
+
+pub fn leave_game_over(
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut game_state: ResMut<State<GameState>>,
+) {
+    if keyboard_input.clear_just_pressed(KeyCode::Return) {
+        let _ = game_state.set(GameState::Title);
+    }
+}
+
+pub fn leave_stage_clear(
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut game_state: ResMut<State<GameState>>,
+    mut current_level: ResMut<CurrentLevel>,
+) {
+    if keyboard_input.clear_just_pressed(KeyCode::Return) {
+        if current_level.index + 1 < LEVELS.len() {
+            let next_index = current_level.index + 1;
+            current_level.go_to(next_index);
+            let _ = game_state.set(GameState::Playing);
+        } else {
+            let _ = game_state.set(GameState::Title);
+        }
+    }
+}
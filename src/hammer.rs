@@ -0,0 +1,88 @@
+use bevy::{
+    prelude::{
+        AssetServer, Assets, Commands, Component, Entity, EventReader, EventWriter, Query, Res,
+        Transform, Vec2, With, Without, default,
+    },
+    sprite::{SpriteSheetBundle, TextureAtlas},
+    time::Time,
+};
+
+use crate::{
+    collision::{Collider, MarioHammerCollisionEvent},
+    mario::Mario,
+    physics::{PhysicsConfig, Velocity},
+    power::{Invulnerable, MarioPowerEvent, PowerChange},
+    star::Starman,
+    tilemap::TileMap,
+};
+
+const HAMMER_SIZE: Vec2 = Vec2::new(8.0, 8.0);
+const HAMMER_THROW_VELOCITY: Vec2 = Vec2::new(70.0, 250.0);
+/// Radians per second.
+const HAMMER_SPIN: f32 = 12.0;
+
+/// Thrown by Hammer Bros. It flies through everything and hurts Mario on
+/// contact. `owner` is the enemy that threw it.
+#[derive(Component)]
+pub struct Hammer {
+    pub owner: Entity,
+}
+
+pub fn get_hammer_bundle(
+    asset_server: &AssetServer,
+    texture_atlases: &mut Assets<TextureAtlas>,
+    owner: Entity,
+    go_right: bool,
+    x: f32,
+    y: f32,
+) -> (SpriteSheetBundle, Velocity, Collider, Hammer) {
+    let hammer_texture_atlas =
+        TextureAtlas::from_grid(asset_server.load("hammer.png"), HAMMER_SIZE, 1, 1, None, None);
+    let direction_x = if go_right { 1.0 } else { -1.0 };
+
+    (
+        SpriteSheetBundle {
+            texture_atlas: texture_atlases.add(hammer_texture_atlas),
+            transform: Transform::from_xyz(x, y, 3.5),
+            ..default()
+        },
+        Velocity(Vec2::new(direction_x * HAMMER_THROW_VELOCITY.x, HAMMER_THROW_VELOCITY.y)),
+        Collider::new(HAMMER_SIZE),
+        Hammer { owner },
+    )
+}
+
+pub fn move_hammers(
+    mut commands: Commands,
+    time: Res<Time>,
+    physics: Res<PhysicsConfig>,
+    tilemap: Res<TileMap>,
+    mut hammer_query: Query<(Entity, &mut Transform, &mut Velocity), With<Hammer>>,
+) {
+    let delta_seconds = time.delta_seconds();
+
+    for (entity, mut transform, mut velocity) in hammer_query.iter_mut() {
+        velocity.y -= physics.projectile_gravity * delta_seconds;
+        transform.translation += velocity.0.extend(0.0) * delta_seconds;
+        transform.rotate_z(-velocity.x.signum() * HAMMER_SPIN * delta_seconds);
+
+        if transform.translation.y + HAMMER_SIZE.y / 2.0 < tilemap.bottom() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+pub fn mario_hammer_collision_event_read(
+    mut mario_hammer_collision_event_reader: EventReader<MarioHammerCollisionEvent>,
+    mut mario_power_event_writer: EventWriter<MarioPowerEvent>,
+    mario_query: Query<Option<&Starman>, (With<Mario>, Without<Invulnerable>)>,
+) {
+    let is_hit = mario_hammer_collision_event_reader.iter().count() > 0;
+    let is_vulnerable = matches!(mario_query.get_single(), Ok(None));
+
+    if is_hit && is_vulnerable {
+        mario_power_event_writer.send(MarioPowerEvent {
+            change: PowerChange::Damage,
+        });
+    }
+}
@@ -0,0 +1,98 @@
+use bevy::prelude::{
+    AlignItems, AssetServer, Assets, BuildChildren, ChildBuilder, Color, Commands, Component,
+    Handle, Font, FlexDirection, JustifyContent, NodeBundle, Query, Res, Size, Style, Text,
+    TextAlignment, TextBundle, TextSection, TextStyle, UiRect, Val, With, Without, default,
+};
+
+use crate::{
+    game_state::{LevelScoped, FONT},
+    level::{CurrentLevel, Level},
+    score::Score,
+};
+
+#[derive(Component)]
+pub struct HudScore;
+
+#[derive(Component)]
+pub struct HudCoins;
+
+#[derive(Component)]
+pub struct HudWorld;
+
+#[derive(Component)]
+pub struct HudTime;
+
+fn spawn_hud_column<T: Component>(parent: &mut ChildBuilder, font: &Handle<Font>, marker: T, label: &str) {
+    let style = TextStyle {
+        font: font.clone(),
+        font_size: 10.0,
+        color: Color::WHITE,
+    };
+
+    parent.spawn((
+        TextBundle {
+            text: Text::from_sections([
+                TextSection::new(format!("{}\n", label), style.clone()),
+                TextSection::from_style(style),
+            ])
+            .with_alignment(TextAlignment::CENTER),
+            ..default()
+        },
+        marker,
+    ));
+}
+
+pub fn spawn_hud(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let font = asset_server.load(FONT);
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                    flex_direction: FlexDirection::Row,
+                    justify_content: JustifyContent::SpaceAround,
+                    align_items: AlignItems::FlexEnd,
+                    padding: UiRect::all(Val::Px(4.0)),
+                    ..default()
+                },
+                ..default()
+            },
+            LevelScoped,
+        ))
+        .with_children(|parent| {
+            spawn_hud_column(parent, &font, HudScore, "MARIO");
+            spawn_hud_column(parent, &font, HudCoins, "COINS");
+            spawn_hud_column(parent, &font, HudWorld, "WORLD");
+            spawn_hud_column(parent, &font, HudTime, "TIME");
+        });
+}
+
+#[allow(clippy::type_complexity)]
+pub fn update_hud(
+    score: Res<Score>,
+    levels: Res<Assets<Level>>,
+    current_level: Res<CurrentLevel>,
+    mut score_query: Query<&mut Text, (With<HudScore>, Without<HudCoins>, Without<HudWorld>, Without<HudTime>)>,
+    mut coins_query: Query<&mut Text, (With<HudCoins>, Without<HudWorld>, Without<HudTime>)>,
+    mut world_query: Query<&mut Text, (With<HudWorld>, Without<HudTime>)>,
+    mut time_query: Query<&mut Text, With<HudTime>>,
+) {
+    for mut text in score_query.iter_mut() {
+        text.sections[1].value = format!("{:06}", score.points);
+    }
+
+    for mut text in coins_query.iter_mut() {
+        text.sections[1].value = format!("x{:02}", score.coins);
+    }
+
+    if let Some(level) = levels.get(&current_level.handle) {
+        for mut text in world_query.iter_mut() {
+            text.sections[1].value = level.name.clone();
+        }
+    }
+
+    for mut text in time_query.iter_mut() {
+        text.sections[1].value = format!("{:03}", score.time);
+    }
+}
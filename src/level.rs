@@ -0,0 +1,502 @@
+use bevy::{
+    asset::{AssetLoader, Error, LoadContext, LoadedAsset},
+    audio::AudioSink,
+    prelude::{AssetServer, Assets, Audio, Camera2d, Commands, Handle, Query, Res, ResMut, Resource, SpriteBundle, Transform, Vec2, Vec3, With, default},
+    reflect::TypeUuid,
+    sprite::TextureAtlas,
+    utils::BoxedFuture,
+    window::Windows,
+};
+use serde::Deserialize;
+
+use crate::{
+    camera::{clamp_camera_x, CameraFollow},
+    behavior::EnemyBehavior,
+    enemy::{spawn_enemy, EnemyType, MarioLevelMusicController},
+    game_state::LevelScoped,
+    level_timer::LevelTimer,
+    mario::{get_mario_bundle, Mario},
+    power::MarioPowerState,
+    score::Score,
+    brick::get_brick_block_bundle,
+    question_block::{get_empty_block_bundle, get_question_block_bundle},
+    tilemap::{Tile, TileMap},
+};
+
+/// Levels in the order they are played.
+pub const LEVELS: &[&str] = &["levels/1-1.level.ron"];
+
+#[derive(Deserialize, TypeUuid)]
+#[uuid = "4c5b7a38-2f0e-4d8a-9a43-6b1f1d8e2c71"]
+pub struct Level {
+    /// Shown under WORLD on the HUD.
+    pub name: String,
+    /// Seconds on the clock when the level starts.
+    pub time_limit: u32,
+    pub background: String,
+    pub music: String,
+    /// Replaces `music` once the clock runs low.
+    pub hurry_music: String,
+    pub mario_start: LevelPosition,
+    /// Once Mario walks past a checkpoint's X he respawns there instead of at
+    /// `mario_start` after losing a life.
+    pub checkpoints: Vec<LevelPosition>,
+    pub can_scroll_back: bool,
+    pub tiles: LevelTiles,
+    pub entities: Vec<LevelEntity>,
+}
+
+/// Hand-written collision grid: `.` is empty and `#` is solid. Blocks listed
+/// in `entities` make their own tile solid, so they don't need marking here.
+#[derive(Deserialize)]
+pub struct LevelTiles {
+    pub origin: LevelPosition,
+    pub rows: Vec<String>,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+pub struct LevelPosition {
+    pub x: f32,
+    pub y: f32,
+}
+
+#[derive(Deserialize)]
+pub struct LevelEntity {
+    pub kind: LevelEntityKind,
+    pub x: f32,
+    pub y: f32,
+    /// Replaces an enemy's default behaviors. Only enemies can have any.
+    #[serde(default)]
+    pub behaviors: Vec<EnemyBehavior>,
+}
+
+#[derive(Deserialize, Debug)]
+pub enum LevelEntityKind {
+    Enemy(EnemyType),
+    QuestionBlock(BlockContents),
+    /// An invisible question block that only shows up once Mario hits it
+    /// from below.
+    HiddenBlock(BlockContents),
+    EmptyBlock,
+    Brick,
+}
+
+/// What comes out of a question block when Mario hits it.
+#[derive(Deserialize, PartialEq, Clone, Copy, Debug)]
+pub enum BlockContents {
+    Coin,
+    /// Gives a coin on every hit, up to `MULTI_COIN_HITS` of them.
+    MultiCoin,
+    /// A mushroom, or a fire flower if Mario is already super.
+    Mushroom,
+    FireFlower,
+    Star,
+    OneUp,
+    Vine,
+}
+
+#[derive(Resource, Default)]
+pub struct CurrentLevel {
+    pub index: usize,
+    pub handle: Handle<Level>,
+    pub is_spawned: bool,
+    /// Set when an enemy dies, until the stage is cleared or restarted.
+    pub has_enemy_died: bool,
+    pub checkpoint: Option<LevelPosition>,
+}
+
+impl CurrentLevel {
+    /// Moves on to the level at `index`, forgetting any checkpoint reached in
+    /// the previous one.
+    pub fn go_to(&mut self, index: usize) {
+        self.index = index;
+        self.checkpoint = None;
+    }
+}
+
+#[derive(Default)]
+pub struct LevelLoader;
+
+impl AssetLoader for LevelLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), Error>> {
+        Box::pin(async move {
+            let path = load_context.path().display().to_string();
+            let level: Level = ron::de::from_bytes(bytes)
+                .map_err(|error| Error::msg(format!("{}: {}", path, error)))?;
+            validate_level(&level).map_err(|error| Error::msg(format!("{}: {}", path, error)))?;
+            load_context.set_default_asset(LoadedAsset::new(level));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["level.ron"]
+    }
+}
+
+fn validate_level(level: &Level) -> Result<(), String> {
+    if !level.mario_start.x.is_finite() || !level.mario_start.y.is_finite() {
+        return Err("mario_start must be a finite position".to_string());
+    }
+
+    if let Some(index) = level
+        .checkpoints
+        .iter()
+        .position(|checkpoint| !checkpoint.x.is_finite() || !checkpoint.y.is_finite())
+    {
+        return Err(format!("checkpoints[{}] must be a finite position", index));
+    }
+
+    if !level.tiles.origin.x.is_finite() || !level.tiles.origin.y.is_finite() {
+        return Err("tiles.origin must be a finite position".to_string());
+    }
+
+    for (row_index, row) in level.tiles.rows.iter().enumerate() {
+        if row.chars().count() != level.tiles.rows[0].chars().count() {
+            return Err(format!(
+                "tiles.rows[{}] has {} tiles but tiles.rows[0] has {}",
+                row_index,
+                row.chars().count(),
+                level.tiles.rows[0].chars().count()
+            ));
+        }
+
+        if let Some((column, character)) = row
+            .chars()
+            .enumerate()
+            .find(|(_, character)| Tile::from_char(*character).is_none())
+        {
+            return Err(format!(
+                "tiles.rows[{}] column {}: unknown tile '{}', expected '.' or '#'",
+                row_index, column, character
+            ));
+        }
+    }
+
+    for (index, entity) in level.entities.iter().enumerate() {
+        if !entity.x.is_finite() || !entity.y.is_finite() {
+            return Err(format!(
+                "entities[{}] ({:?}) must have a finite position",
+                index,
+                entity.kind
+            ));
+        }
+
+        if !entity.behaviors.is_empty() && !matches!(entity.kind, LevelEntityKind::Enemy(_)) {
+            return Err(format!(
+                "entities[{}] ({:?}) can't have behaviors, only enemies can",
+                index,
+                entity.kind
+            ));
+        }
+
+        if !entity.kind.is_block() {
+            continue;
+        }
+
+        // Every block fills its tile, so two blocks can never share a spot.
+        if let Some(other) = level.entities[..index]
+            .iter()
+            .position(|other| other.kind.is_block() && other.x == entity.x && other.y == entity.y)
+        {
+            return Err(format!(
+                "entities[{}] ({:?} at {}, {}) overlaps entities[{}]",
+                index,
+                entity.kind,
+                entity.x,
+                entity.y,
+                other
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+impl LevelEntityKind {
+    fn is_block(&self) -> bool {
+        matches!(
+            self,
+            LevelEntityKind::QuestionBlock(_)
+                | LevelEntityKind::HiddenBlock(_)
+                | LevelEntityKind::EmptyBlock
+                | LevelEntityKind::Brick
+        )
+    }
+}
+
+pub fn start_level(asset_server: Res<AssetServer>, mut current_level: ResMut<CurrentLevel>) {
+    current_level.handle = asset_server.load(LEVELS[current_level.index]);
+    current_level.is_spawned = false;
+    current_level.has_enemy_died = false;
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn spawn_level(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    audio: Res<Audio>,
+    audio_sinks: Res<Assets<AudioSink>>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    levels: Res<Assets<Level>>,
+    mut current_level: ResMut<CurrentLevel>,
+    mut score: ResMut<Score>,
+    windows: Res<Windows>,
+    mut camera_query: Query<&mut Transform, With<Camera2d>>,
+) {
+    if current_level.is_spawned {
+        return;
+    }
+
+    let level = match levels.get(&current_level.handle) {
+        Some(level) => level,
+        None => return,
+    };
+    current_level.is_spawned = true;
+    score.time = level.time_limit;
+    commands.insert_resource(LevelTimer::default());
+
+    let mario_start = current_level.checkpoint.unwrap_or(level.mario_start);
+
+    let mut tilemap = TileMap::new(
+        Vec2::new(level.tiles.origin.x, level.tiles.origin.y),
+        &level.tiles.rows,
+    );
+
+    let music = asset_server.load(level.music.as_str());
+    let handle = audio_sinks.get_handle(audio.play(music));
+    commands.insert_resource(MarioLevelMusicController(handle));
+
+    commands.spawn((
+        SpriteBundle {
+            texture: asset_server.load(level.background.as_str()),
+            transform: Transform::from_scale(Vec3::new(1.0, 1.0, 0.0)),
+            ..default()
+        },
+        LevelScoped,
+    ));
+
+    for entity in level.entities.iter() {
+        match &entity.kind {
+            LevelEntityKind::Enemy(enemy_type) => {
+                spawn_enemy(
+                    &mut commands,
+                    &asset_server,
+                    &mut texture_atlases,
+                    *enemy_type,
+                    &entity.behaviors,
+                    entity.x,
+                    entity.y,
+                );
+            }
+            LevelEntityKind::QuestionBlock(contents) | LevelEntityKind::HiddenBlock(contents) => {
+                let is_hidden = matches!(entity.kind, LevelEntityKind::HiddenBlock(_));
+                let tile = if is_hidden { Tile::Hidden } else { Tile::Solid };
+                tilemap.set_tile_at(entity.x, entity.y, tile);
+                commands.spawn((
+                    get_question_block_bundle(
+                        &asset_server,
+                        &mut texture_atlases,
+                        *contents,
+                        !is_hidden,
+                        entity.x,
+                        entity.y,
+                    ),
+                    LevelScoped,
+                ));
+            }
+            LevelEntityKind::EmptyBlock => {
+                tilemap.set_tile_at(entity.x, entity.y, Tile::Solid);
+                commands.spawn((
+                    get_empty_block_bundle(&asset_server, &mut texture_atlases, entity.x, entity.y),
+                    LevelScoped,
+                ));
+            }
+            LevelEntityKind::Brick => {
+                tilemap.set_tile_at(entity.x, entity.y, Tile::Solid);
+                commands.spawn((
+                    get_brick_block_bundle(&asset_server, &mut texture_atlases, entity.x, entity.y),
+                    LevelScoped,
+                ));
+            }
+        }
+    }
+
+    if let Some(window) = windows.get_primary() {
+        for mut camera_transform in camera_query.iter_mut() {
+            camera_transform.translation.x =
+                clamp_camera_x(mario_start.x, &tilemap, window.width() / 2.0);
+        }
+    }
+
+    commands.insert_resource(CameraFollow {
+        can_scroll_back: level.can_scroll_back,
+        ..default()
+    });
+    commands.insert_resource(tilemap);
+
+    commands.spawn((
+        get_mario_bundle(
+            &asset_server,
+            &mut texture_atlases,
+            MarioPowerState::Small,
+            mario_start.x,
+            mario_start.y,
+        ),
+        LevelScoped,
+    ));
+}
+
+pub fn reach_checkpoints(
+    levels: Res<Assets<Level>>,
+    mut current_level: ResMut<CurrentLevel>,
+    mario_query: Query<&Transform, With<Mario>>,
+) {
+    let level = match levels.get(&current_level.handle) {
+        Some(level) => level,
+        None => return,
+    };
+
+    for mario_transform in mario_query.iter() {
+        let reached_x = current_level
+            .checkpoint
+            .map_or(f32::NEG_INFINITY, |checkpoint| checkpoint.x);
+
+        if let Some(checkpoint) = level
+            .checkpoints
+            .iter()
+            .rev()
+            .find(|checkpoint| {
+                checkpoint.x > reached_x && mario_transform.translation.x >= checkpoint.x
+            })
+        {
+            current_level.checkpoint = Some(*checkpoint);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn level(rows: &[&str], entities: &str) -> Level {
+        let rows: Vec<String> = rows.iter().map(|row| format!("{:?}", row)).collect();
+        ron::from_str(&format!(
+            r#"(
+                name: "test",
+                time_limit: 400,
+                background: "map.png",
+                music: "level1_music.ogg",
+                hurry_music: "hurry_up.wav",
+                mario_start: (x: 0.0, y: 0.0),
+                checkpoints: [(x: 40.0, y: 0.0)],
+                can_scroll_back: false,
+                tiles: (origin: (x: 0.0, y: 0.0), rows: [{}]),
+                entities: [{}],
+            )"#,
+            rows.join(", "),
+            entities
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn accepts_valid_level() {
+        let level = level(
+            &[".....", "#####"],
+            r#"(kind: Enemy(Goomba), x: 8.0, y: -8.0, behaviors: [Walker(speed: 20.0)]),
+               (kind: QuestionBlock(Coin), x: 24.0, y: -8.0),
+               (kind: Brick, x: 40.0, y: -8.0)"#,
+        );
+
+        assert_eq!(validate_level(&level), Ok(()));
+    }
+
+    #[test]
+    fn rejects_non_finite_mario_start() {
+        let mut level = level(&["#####"], "");
+        level.mario_start.x = f32::NAN;
+
+        assert_eq!(validate_level(&level), Err("mario_start must be a finite position".to_string()));
+    }
+
+    #[test]
+    fn rejects_non_finite_checkpoint() {
+        let mut level = level(&["#####"], "");
+        level.checkpoints[0].y = f32::INFINITY;
+
+        assert_eq!(validate_level(&level), Err("checkpoints[0] must be a finite position".to_string()));
+    }
+
+    #[test]
+    fn rejects_rows_of_different_lengths() {
+        let level = level(&[".....", "####"], "");
+
+        assert_eq!(
+            validate_level(&level),
+            Err("tiles.rows[1] has 4 tiles but tiles.rows[0] has 5".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_tile() {
+        let level = level(&["..?.."], "");
+
+        assert_eq!(
+            validate_level(&level),
+            Err("tiles.rows[0] column 2: unknown tile '?', expected '.' or '#'".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_non_finite_entity_position() {
+        let mut level = level(&["#####"], "(kind: Brick, x: 8.0, y: -8.0)");
+        level.entities[0].x = f32::NAN;
+
+        assert_eq!(
+            validate_level(&level),
+            Err("entities[0] (Brick) must have a finite position".to_string())
+        );
+    }
+
+    #[test]
+    fn names_enemies_by_type() {
+        let mut level = level(&["#####"], "(kind: Enemy(PiranhaPlant), x: 8.0, y: -8.0)");
+        level.entities[0].y = f32::NAN;
+
+        assert_eq!(
+            validate_level(&level),
+            Err("entities[0] (Enemy(PiranhaPlant)) must have a finite position".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_behaviors_on_blocks() {
+        let level = level(
+            &["#####"],
+            "(kind: Brick, x: 8.0, y: -8.0, behaviors: [Flyer])",
+        );
+
+        assert_eq!(
+            validate_level(&level),
+            Err("entities[0] (Brick) can't have behaviors, only enemies can".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_overlapping_blocks() {
+        let level = level(
+            &["#####"],
+            "(kind: Brick, x: 8.0, y: -8.0), (kind: Enemy(Goomba), x: 8.0, y: -8.0), (kind: EmptyBlock, x: 8.0, y: -8.0)",
+        );
+
+        assert_eq!(
+            validate_level(&level),
+            Err("entities[2] (EmptyBlock at 8, -8) overlaps entities[0]".to_string())
+        );
+    }
+}
@@ -0,0 +1,89 @@
+use bevy::{
+    audio::AudioSink,
+    prelude::{AssetServer, Assets, Audio, EventWriter, PlaybackSettings, Query, Res, ResMut, Resource, Transform, With},
+    time::{Time, Timer, TimerMode},
+};
+
+use crate::{
+    enemy::MarioLevelMusicController,
+    level::{CurrentLevel, Level},
+    mario::{Mario, MarioDeadEvent},
+    score::Score,
+    star::Starman,
+};
+
+/// Seconds left at which the level's hurry-up music takes over to hurry Mario
+/// along.
+const HURRY_UP_TIME: u32 = 100;
+const TIME_BONUS_POINTS: u32 = 50;
+
+/// Counts `Score::time` down once a second while Mario is alive.
+#[derive(Resource)]
+pub struct LevelTimer {
+    timer: Timer,
+    is_hurried: bool,
+}
+
+impl Default for LevelTimer {
+    fn default() -> Self {
+        LevelTimer {
+            timer: Timer::from_seconds(1.0, TimerMode::Repeating),
+            is_hurried: false,
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn count_down_level_time(
+    time: Res<Time>,
+    mut level_timer: ResMut<LevelTimer>,
+    mut score: ResMut<Score>,
+    asset_server: Res<AssetServer>,
+    audio: Res<Audio>,
+    audio_sinks: Res<Assets<AudioSink>>,
+    mut music_controller: ResMut<MarioLevelMusicController>,
+    current_level: Res<CurrentLevel>,
+    levels: Res<Assets<Level>>,
+    mario_query: Query<(&Transform, Option<&Starman>), With<Mario>>,
+    mut mario_dead_event_writer: EventWriter<MarioDeadEvent>,
+) {
+    let (mario_transform, starman) = match mario_query.get_single() {
+        Ok(mario) => mario,
+        Err(_) => return,
+    };
+
+    // The star theme has the music to itself, so the switch waits for it to
+    // wear off.
+    if score.time <= HURRY_UP_TIME && !level_timer.is_hurried && starman.is_none() {
+        if let Some(level) = levels.get(&current_level.handle) {
+            level_timer.is_hurried = true;
+            if let Some(sink) = audio_sinks.get(&music_controller.0) {
+                sink.stop();
+            }
+            let hurry_music = audio.play_with_settings(
+                asset_server.load(level.hurry_music.as_str()),
+                PlaybackSettings::LOOP,
+            );
+            music_controller.0 = audio_sinks.get_handle(hurry_music);
+        }
+    }
+
+    if score.time == 0 || !level_timer.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    score.time -= 1;
+
+    if score.time == 0 {
+        mario_dead_event_writer.send(MarioDeadEvent {
+            x: mario_transform.translation.x,
+            y: mario_transform.translation.y,
+        });
+    }
+}
+
+/// Turns whatever time is left on the clock into points once the stage is clear.
+pub fn add_time_bonus(mut score: ResMut<Score>) {
+    score.points += score.time * TIME_BONUS_POINTS;
+    score.time = 0;
+}
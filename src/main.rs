@@ -1,17 +1,57 @@
 mod enemy;
 mod animation;
+mod behavior;
+mod block_bump;
+mod brick;
+mod camera;
+mod coin;
+mod collision;
+mod fire_flower;
+mod fireball;
 mod firework;
+mod game_state;
+mod hammer;
+mod hud;
+mod level;
+mod level_timer;
 mod mario;
 mod mushroom;
+mod physics;
+mod power;
 mod question_block;
+mod score;
+mod shell;
+mod star;
+mod tilemap;
+mod vine;
 
-use animation::{AnimationTimer, AnimationIndices};
-use bevy::{audio::AudioSink, prelude::*};
-use enemy::{animate_enemies, move_enemy, enemy_move_event_read_for_mario, EnemyDead, EnemyMoveEvent, enemy_dead_event_read, EnemyType, Enemy, MarioLevelMusicController};
-use firework::{animate_fireworks, show_fireworks};
-use mario::{move_mario, handle_mario_dead_event, MarioMoveEvent, MarioChangedAsSuperMarioEvent, mario_move_event_read, mario_changed_as_supermario_event_read, get_mario_bundle};
-use mushroom::{move_mushroom, magic_mushroom_event_read, MagicMushroomReleaseEvent, MagicMushroom};
-use question_block::{animate_question_blocks, hit_questionblock_by_mario, EmptyBlock, QuestionBlock};
+use animation::{animate_sprites, AnimationFinishedEvent};
+use behavior::{bounce_enemies, chase_mario, dwell_in_pipes, jump_enemies, shoot_projectiles, spawn_pipe_covers, turn_colliding_enemies, walk_enemies};
+use bevy::prelude::*;
+use block_bump::{animate_block_bumps, knock_out_from_below, BlockBumpEvent};
+use brick::{hit_bricks, move_debris};
+use camera::{follow_mario, CameraFollow};
+use coin::move_block_coins;
+use collision::{detect_collisions, EnemyCollisionEvent, FireballEnemyCollisionEvent, MarioEnemyCollisionEvent, MarioHammerCollisionEvent, MarioItemCollisionEvent};
+use enemy::{activate_enemies, move_enemy, move_knocked_out_enemies, tick_squashed_enemies, mario_enemy_collision_event_read, EnemyDead, clear_stage_when_enemies_gone, start_stage_clear, MarioLevelMusicController};
+use fire_flower::{fire_flower_release_event_read, grow_fire_flowers, FireFlowerReleaseEvent};
+use fireball::{fireball_enemy_collision_event_read, move_fireballs, throw_fireballs};
+use firework::{despawn_fireworks, show_fireworks};
+use game_state::{despawn_with, leave_game_over, leave_stage_clear, pause_game, pause_music, resume_game, resume_music, spawn_game_over_screen, spawn_pause_screen, spawn_stage_clear_screen, spawn_title_screen, start_game, GameOverScreen, GameState, LevelScoped, Lives, PauseScreen, StageClearScreen, TitleScreen};
+use hammer::{mario_hammer_collision_event_read, move_hammers};
+use hud::{spawn_hud, update_hud};
+use level::{reach_checkpoints, spawn_level, start_level, CurrentLevel, Level, LevelLoader};
+use level_timer::{add_time_bonus, count_down_level_time, LevelTimer};
+use mario::{animate_mario, move_mario, handle_mario_dead_event, MarioHeadBumpEvent, MarioDeadEvent, mario_item_collision_event_read, mario_dead_event_read};
+use mushroom::{move_mushroom, magic_mushroom_event_read, MagicMushroomReleaseEvent};
+use physics::PhysicsConfig;
+use power::{animate_power_transition, change_mario_power, tick_invulnerability, MarioPowerEvent};
+use question_block::hit_questionblock_by_mario;
+use score::{add_score, collect_coins, move_score_popups, CoinEvent, Score, ScoreEvent};
+use shell::{move_shells, shell_hit_enemies};
+use star::{collect_stars, move_stars, star_release_event_read, stop_star_music, tick_starman, StarMusicController, StarReleaseEvent};
+use tilemap::TileMap;
+use vine::{grow_vines, vine_release_event_read, VineReleaseEvent};
 
 fn main() {
     let window = WindowDescriptor {
@@ -31,239 +71,145 @@ fn main() {
                 })
                 .set(ImagePlugin::default_nearest()),
         )
+        .add_asset::<Level>()
+        .init_asset_loader::<LevelLoader>()
+        .init_resource::<CurrentLevel>()
+        .init_resource::<Lives>()
+        .init_resource::<Score>()
+        .init_resource::<LevelTimer>()
+        .init_resource::<TileMap>()
+        .init_resource::<PhysicsConfig>()
+        .init_resource::<CameraFollow>()
+        .init_resource::<MarioLevelMusicController>()
+        .init_resource::<StarMusicController>()
+        .add_state(GameState::Title)
         .add_startup_system(setup)
-        .add_system(animate_enemies)
-        .add_system(animate_question_blocks)
-        .add_system(animate_fireworks)
-        .add_system(show_fireworks)
-        .add_system(move_mario)
-        .add_system(move_enemy)
-        .add_system(handle_mario_dead_event.after(enemy_move_event_read_for_mario))
-        .add_system(hit_questionblock_by_mario)
-        .add_system(magic_mushroom_event_read.after(hit_questionblock_by_mario))
-        .add_system(move_mushroom.after(magic_mushroom_event_read))
-        .add_system(mario_move_event_read.after(move_mario))
-        .add_system(enemy_move_event_read_for_mario.after(move_enemy))
-        .add_system(enemy_dead_event_read.after(enemy_move_event_read_for_mario))
-        .add_system(mario_changed_as_supermario_event_read.after(mario_move_event_read))
+        .add_system_set(SystemSet::on_enter(GameState::Title).with_system(spawn_title_screen))
+        .add_system_set(SystemSet::on_update(GameState::Title).with_system(start_game))
+        .add_system_set(SystemSet::on_exit(GameState::Title).with_system(despawn_with::<TitleScreen>))
+        .add_system_set(
+            SystemSet::on_enter(GameState::Playing)
+                .with_system(despawn_with::<LevelScoped>)
+                .with_system(start_level)
+                .with_system(spawn_hud.after(despawn_with::<LevelScoped>)),
+        )
+        .add_system_set(
+            SystemSet::on_update(GameState::Playing)
+                .with_system(spawn_level)
+                .with_system(pause_game)
+                .with_system(move_mario)
+                .with_system(animate_mario.after(move_mario))
+                .with_system(reach_checkpoints.after(move_mario))
+                .with_system(follow_mario.after(move_mario))
+                .with_system(activate_enemies.after(follow_mario))
+                .with_system(walk_enemies.after(activate_enemies))
+                .with_system(chase_mario.after(activate_enemies))
+                .with_system(move_enemy.after(walk_enemies).after(chase_mario))
+                .with_system(jump_enemies.after(move_enemy))
+                .with_system(bounce_enemies.after(move_enemy))
+                .with_system(shoot_projectiles.after(walk_enemies))
+                .with_system(dwell_in_pipes.after(activate_enemies))
+                .with_system(spawn_pipe_covers)
+                .with_system(move_hammers.after(shoot_projectiles))
+                .with_system(handle_mario_dead_event.after(mario_enemy_collision_event_read))
+                .with_system(mario_dead_event_read.after(move_mario).after(mario_enemy_collision_event_read).after(count_down_level_time).after(change_mario_power))
+                .with_system(hit_questionblock_by_mario.after(move_mario))
+                .with_system(hit_bricks.after(move_mario))
+                .with_system(move_debris)
+                .with_system(move_block_coins.after(hit_questionblock_by_mario))
+                .with_system(animate_block_bumps.after(hit_questionblock_by_mario).after(hit_bricks))
+                .with_system(knock_out_from_below.after(hit_questionblock_by_mario).after(hit_bricks))
+                .with_system(move_knocked_out_enemies)
+                .with_system(tick_squashed_enemies)
+                .with_system(magic_mushroom_event_read.after(hit_questionblock_by_mario))
+                .with_system(move_mushroom.after(magic_mushroom_event_read))
+                .with_system(mario_item_collision_event_read.after(detect_collisions))
+                .with_system(move_shells.after(move_enemy))
+                .with_system(throw_fireballs.after(move_mario))
+                .with_system(move_fireballs.after(throw_fireballs))
+                .with_system(fire_flower_release_event_read.after(hit_questionblock_by_mario))
+                .with_system(grow_fire_flowers.after(magic_mushroom_event_read).after(fire_flower_release_event_read))
+                .with_system(vine_release_event_read.after(hit_questionblock_by_mario))
+                .with_system(grow_vines.after(vine_release_event_read))
+                .with_system(star_release_event_read.after(hit_questionblock_by_mario))
+                .with_system(move_stars.after(star_release_event_read))
+                .with_system(detect_collisions.after(move_mario).after(move_shells).after(move_mushroom).after(move_fireballs).after(grow_fire_flowers).after(move_stars).after(move_enemy).after(jump_enemies).after(bounce_enemies).after(dwell_in_pipes).after(move_hammers))
+                .with_system(fireball_enemy_collision_event_read.after(detect_collisions))
+                .with_system(mario_enemy_collision_event_read.after(detect_collisions))
+                .with_system(mario_hammer_collision_event_read.after(detect_collisions))
+                .with_system(shell_hit_enemies.after(mario_enemy_collision_event_read))
+                .with_system(turn_colliding_enemies.after(detect_collisions))
+                .with_system(animate_sprites.after(animate_mario).after(mario_enemy_collision_event_read))
+                .with_system(clear_stage_when_enemies_gone.after(move_enemy).after(move_shells).after(mario_enemy_collision_event_read).after(shell_hit_enemies).after(fireball_enemy_collision_event_read).after(knock_out_from_below))
+                .with_system(change_mario_power.after(mario_item_collision_event_read).after(mario_enemy_collision_event_read).after(mario_hammer_collision_event_read))
+                .with_system(tick_invulnerability)
+                .with_system(collect_stars.after(detect_collisions).before(mario_dead_event_read))
+                .with_system(tick_starman.after(collect_stars))
+                .with_system(collect_coins.after(hit_questionblock_by_mario))
+                .with_system(add_score.after(collect_coins).after(fireball_enemy_collision_event_read).after(shell_hit_enemies).after(mario_enemy_collision_event_read).after(mario_item_collision_event_read).after(collect_stars).after(hit_bricks).after(knock_out_from_below))
+                .with_system(move_score_popups)
+                .with_system(count_down_level_time.after(spawn_level))
+                .with_system(update_hud.after(add_score).after(count_down_level_time)),
+        )
+        .add_system_set(SystemSet::on_exit(GameState::Playing).with_system(stop_star_music))
+        .add_system_set(
+            SystemSet::on_enter(GameState::Paused)
+                .with_system(spawn_pause_screen)
+                .with_system(pause_music),
+        )
+        .add_system_set(SystemSet::on_update(GameState::Paused).with_system(resume_game))
+        .add_system_set(
+            SystemSet::on_exit(GameState::Paused)
+                .with_system(despawn_with::<PauseScreen>)
+                .with_system(resume_music),
+        )
+        .add_system_set(SystemSet::on_update(GameState::Transforming).with_system(animate_power_transition))
+        .add_system_set(SystemSet::on_enter(GameState::GameOver).with_system(spawn_game_over_screen))
+        .add_system_set(SystemSet::on_update(GameState::GameOver).with_system(leave_game_over))
+        .add_system_set(
+            SystemSet::on_exit(GameState::GameOver)
+                .with_system(despawn_with::<GameOverScreen>)
+                .with_system(despawn_with::<LevelScoped>),
+        )
+        .add_system_set(
+            SystemSet::on_enter(GameState::StageClear)
+                .with_system(spawn_stage_clear_screen)
+                .with_system(start_stage_clear)
+                .with_system(add_time_bonus),
+        )
+        .add_system_set(
+            SystemSet::on_update(GameState::StageClear)
+                .with_system(show_fireworks)
+                .with_system(animate_sprites.after(show_fireworks))
+                .with_system(despawn_fireworks.after(animate_sprites))
+                .with_system(update_hud)
+                .with_system(leave_stage_clear),
+        )
+        .add_system_set(
+            SystemSet::on_exit(GameState::StageClear)
+                .with_system(despawn_with::<StageClearScreen>)
+                .with_system(despawn_with::<LevelScoped>),
+        )
         .add_event::<MagicMushroomReleaseEvent>()
-        .add_event::<MarioChangedAsSuperMarioEvent>()
-        .add_event::<MarioMoveEvent>()
-        .add_event::<EnemyMoveEvent>()
+        .add_event::<FireFlowerReleaseEvent>()
+        .add_event::<StarReleaseEvent>()
+        .add_event::<VineReleaseEvent>()
+        .add_event::<MarioPowerEvent>()
+        .add_event::<MarioHeadBumpEvent>()
+        .add_event::<BlockBumpEvent>()
+        .add_event::<MarioDeadEvent>()
+        .add_event::<MarioEnemyCollisionEvent>()
+        .add_event::<MarioItemCollisionEvent>()
+        .add_event::<MarioHammerCollisionEvent>()
+        .add_event::<FireballEnemyCollisionEvent>()
+        .add_event::<EnemyCollisionEvent>()
         .add_event::<EnemyDead>()
+        .add_event::<ScoreEvent>()
+        .add_event::<CoinEvent>()
+        .add_event::<AnimationFinishedEvent>()
         .run();
 }
 
-fn setup(
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
-    audio: Res<Audio>,
-    audio_sinks: Res<Assets<AudioSink>>,
-) {
-    let music = asset_server.load("level1_music.ogg");
-    let handle = audio_sinks.get_handle(audio.play(music));
-    commands.insert_resource(MarioLevelMusicController(handle));
-
-    let background_image = asset_server.load("map.png");
-
-    let questionblock_texture_handle = asset_server.load("question-block.png");
-    let questionblock_texture_atlas = TextureAtlas::from_grid(
-        questionblock_texture_handle,
-        Vec2::new(16.0, 16.0),
-        6,
-        1,
-        None,
-        None,
-    );
-    let questionblock_texture_atlas_handle = texture_atlases.add(questionblock_texture_atlas);
-
-    let emptyblock_texture_handle = asset_server.load("emptyBlock.png");
-    let emptyblock_texture_atlas = TextureAtlas::from_grid(
-        emptyblock_texture_handle,
-        Vec2::new(16.0, 16.0),
-        1,
-        1,
-        None,
-        None,
-    );
-    let emptyblock_texture_atlas_handle = texture_atlases.add(emptyblock_texture_atlas);
-
-    let magicmushroom_texture_handle = asset_server.load("magicMushroom.png");
-    let magicmushroom_texture_atlas = TextureAtlas::from_grid(
-        magicmushroom_texture_handle,
-        Vec2::new(16.0, 16.0),
-        1,
-        1,
-        None,
-        None,
-    );
-    let magicmushroom_texture_atlas_handle = texture_atlases.add(magicmushroom_texture_atlas);
-
-    let goomba_texture_handle = asset_server.load("goomba.png");
-    let goomba_texture_atlas = TextureAtlas::from_grid(
-        goomba_texture_handle,
-        Vec2::new(16.0, 16.0),
-        2,
-        1,
-        None,
-        None,
-    );
-    let goomba_texture_atlas_handle = texture_atlases.add(goomba_texture_atlas);
-
-    let turtle_texture_handle = asset_server.load("turtle.png");
-    let turtle_texture_atlas = TextureAtlas::from_grid(
-        turtle_texture_handle,
-        Vec2::new(16.0, 24.0),
-        2,
-        1,
-        None,
-        None,
-    );
-    let turtle_texture_atlas_handle = texture_atlases.add(turtle_texture_atlas);
-
+fn setup(mut commands: Commands) {
     commands.spawn(Camera2dBundle::default());
-    commands.spawn(SpriteBundle {
-        texture: background_image,
-        transform: Transform::from_scale(Vec3::new(1.0, 1.0, 0.0)),
-        ..default()
-    });
-    commands.spawn((
-        SpriteSheetBundle {
-            texture_atlas: goomba_texture_atlas_handle.clone(),
-            sprite: TextureAtlasSprite::new(0),
-            transform: Transform::from_xyz(175.0, -78.0, 3.0),
-            ..default()
-        },
-        AnimationIndices { first: 0, last: 1 },
-        AnimationTimer(Timer::from_seconds(0.1, TimerMode::Repeating)),
-        Enemy {
-            go_right: false,
-            enemy_type: EnemyType::Goomba,
-        },
-    ));
-    commands.spawn((
-        SpriteSheetBundle {
-            texture_atlas: turtle_texture_atlas_handle.clone(),
-            sprite: TextureAtlasSprite::new(0),
-            transform: Transform::from_xyz(100.0, -74.0, 3.0),
-            ..default()
-        },
-        AnimationIndices { first: 0, last: 1 },
-        AnimationTimer(Timer::from_seconds(0.1, TimerMode::Repeating)),
-        Enemy {
-            go_right: false,
-            enemy_type: EnemyType::Turtle,
-        },
-    ));
-    commands.spawn((
-        SpriteSheetBundle {
-            visibility: Visibility {
-                is_visible: (false),
-            },
-            texture_atlas: magicmushroom_texture_atlas_handle.clone(),
-            transform: Transform::from_xyz(2.0, -29.5, 3.0),
-            ..default()
-        },
-        MagicMushroom {
-            is_released: false,
-            x_reached_max: false,
-        },
-        AnimationTimer(Timer::from_seconds(0.1, TimerMode::Repeating)),
-    ));
-    commands.spawn((
-        SpriteSheetBundle {
-            visibility: Visibility {
-                is_visible: (false),
-            },
-            texture_atlas: emptyblock_texture_atlas_handle.clone(),
-            transform: Transform::from_xyz(2.0, -29.5, 3.0),
-            ..default()
-        },
-        EmptyBlock,
-    ));
-    commands.spawn((
-        SpriteSheetBundle {
-            texture_atlas: questionblock_texture_atlas_handle.clone(),
-            sprite: TextureAtlasSprite::new(0),
-            transform: Transform::from_xyz(2.0, -29.5, 3.0),
-            ..default()
-        },
-        AnimationIndices { first: 0, last: 5 },
-        AnimationTimer(Timer::from_seconds(0.1, TimerMode::Repeating)),
-        QuestionBlock { is_mushroom: true },
-    ));
-    commands.spawn((
-        SpriteSheetBundle {
-            visibility: Visibility {
-                is_visible: (false),
-            },
-            texture_atlas: emptyblock_texture_atlas_handle.clone(),
-            transform: Transform::from_xyz(82.5, -29.5, 3.0),
-            ..default()
-        },
-        EmptyBlock,
-    ));
-    commands.spawn((
-        SpriteSheetBundle {
-            texture_atlas: questionblock_texture_atlas_handle.clone(),
-            sprite: TextureAtlasSprite::new(0),
-            transform: Transform::from_xyz(82.5, -29.5, 3.0),
-            ..default()
-        },
-        AnimationIndices { first: 0, last: 5 },
-        AnimationTimer(Timer::from_seconds(0.1, TimerMode::Repeating)),
-        QuestionBlock { is_mushroom: false },
-    ));
-    commands.spawn((
-        SpriteSheetBundle {
-            visibility: Visibility {
-                is_visible: (false),
-            },
-            texture_atlas: emptyblock_texture_atlas_handle.clone(),
-            transform: Transform::from_xyz(114.5, -29.5, 3.0),
-            ..default()
-        },
-        EmptyBlock,
-    ));
-    commands.spawn((
-        SpriteSheetBundle {
-            texture_atlas: questionblock_texture_atlas_handle.clone(),
-            sprite: TextureAtlasSprite::new(0),
-            transform: Transform::from_xyz(114.5, -29.5, 3.0),
-            ..default()
-        },
-        AnimationIndices { first: 0, last: 5 },
-        AnimationTimer(Timer::from_seconds(0.1, TimerMode::Repeating)),
-        QuestionBlock { is_mushroom: false },
-    ));
-    commands.spawn((
-        SpriteSheetBundle {
-            visibility: Visibility {
-                is_visible: (false),
-            },
-            texture_atlas: emptyblock_texture_atlas_handle.clone(),
-            transform: Transform::from_xyz(98.2, 34.0, 3.0),
-            ..default()
-        },
-        EmptyBlock,
-    ));
-    commands.spawn((
-        SpriteSheetBundle {
-            texture_atlas: questionblock_texture_atlas_handle.clone(),
-            sprite: TextureAtlasSprite::new(0),
-            transform: Transform::from_xyz(98.2, 34.0, 3.0),
-            ..default()
-        },
-        AnimationIndices { first: 0, last: 5 },
-        AnimationTimer(Timer::from_seconds(0.1, TimerMode::Repeating)),
-        QuestionBlock { is_mushroom: false },
-    ));
-    commands.spawn(get_mario_bundle(
-        asset_server,
-        texture_atlases,
-        false,
-        -250.0,
-        -78.0,
-    ));
 }
@@ -1,132 +1,184 @@
-use std::time::Duration;
+use bevy::{prelude::{Component, Res, Audio, AssetServer, Input, KeyCode, Query, Transform, With, Without, EventWriter, ResMut, Assets, Vec2, default, EventReader, Commands, Entity, State}, time::{Time, TimerMode, Timer}, sprite::{TextureAtlasSprite, TextureAtlas, SpriteSheetBundle}, audio::AudioSink};
 
-use bevy::{prelude::{Component, Res, Audio, AssetServer, Input, KeyCode, Query, Transform, With, EventWriter, ResMut, Assets, Vec2, default, EventReader, Visibility, Commands, Entity}, time::{Time, TimerMode, Timer}, sprite::{TextureAtlasSprite, TextureAtlas, SpriteSheetBundle}};
+use crate::{animation::Animation, mushroom::{MagicMushroom, MushroomKind}, fire_flower::FireFlower, tilemap::TileMap, enemy::MarioLevelMusicController, physics::{PhysicsConfig, Velocity, approach}, game_state::{GameState, LevelScoped, Lives}, score::{ScoreEvent, POWER_UP_POINTS}, collision::{collide, Collider, MarioItemCollisionEvent}, power::{MarioPowerEvent, MarioPowerState, PowerChange}, star::StarMusicController, vine::Vine};
 
-use crate::{animation::{AnimationIndices, AnimationTimer}, mushroom::MagicMushroom};
+/// How long the death jump plays before the level ends.
+const MARIO_DEAD_DURATION: f32 = 3.0;
 
 #[derive(Component)]
 pub struct MarioDead {
-    go_up: bool,
+    timer: Timer,
+}
+
+/// What Mario is doing, as far as his sprite goes. `move_mario` keeps it up
+/// to date and `animate_mario` shows the matching clip.
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MarioAnimation {
+    Idle,
+    Walk,
+    Run,
+    Skid,
+    Jump,
+    Crouch,
+    Die,
 }
 
 #[derive(Component)]
 pub struct Mario {
-    dont_go_up_until_settle: bool,
-    pub is_super_mario: bool,
+    is_on_ground: bool,
+    is_jumping: bool,
+    pub power: MarioPowerState,
 }
 
-pub struct MarioChangedAsSuperMarioEvent {
-    x: f32,
-    y: f32,
+/// Sent when Mario's head hits a solid tile from below; `y` is the top of his head.
+pub struct MarioHeadBumpEvent {
+    pub x: f32,
+    pub y: f32,
 }
 
-pub struct MarioMoveEvent {
-    x: f32,
-    y: f32,
+pub struct MarioDeadEvent {
+    pub x: f32,
+    pub y: f32,
 }
 
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 pub fn move_mario(
     time: Res<Time>,
     audio: Res<Audio>,
     asset_server: Res<AssetServer>,
     keyboard_input: Res<Input<KeyCode>>,
+    physics: Res<PhysicsConfig>,
+    tilemap: Res<TileMap>,
     mut mario_query: Query<
         (
-            &AnimationIndices,
             &mut Transform,
             &mut TextureAtlasSprite,
-            &mut AnimationTimer,
+            &mut Velocity,
+            &mut MarioAnimation,
             &mut Mario,
         ),
         With<Mario>,
     >,
-    mut mario_move_event_writer: EventWriter<MarioMoveEvent>,
+    vine_query: Query<(&Transform, &Collider), (With<Vine>, Without<Mario>)>,
+    mut mario_head_bump_event_writer: EventWriter<MarioHeadBumpEvent>,
+    mut mario_dead_event_writer: EventWriter<MarioDeadEvent>,
 ) {
     if mario_query.is_empty() {
         return;
     }
 
-    for (
-        animation_indices,
-        mut transform,
-        mut texture_atlas_sprite,
-        mut animation_timer,
-        mut mario,
-    ) in mario_query.iter_mut()
+    let delta_seconds = time.delta_seconds();
+
+    for (mut transform, mut texture_atlas_sprite, mut velocity, mut mario_animation, mut mario) in
+        mario_query.iter_mut()
     {
+        // Big Mario ducks on the ground and slides to a stop.
+        let is_crouching =
+            mario.power.is_big() && mario.is_on_ground && keyboard_input.pressed(KeyCode::Down);
         let mut direction_x = 0.0;
-        let mut direction_y = 0.0;
-
-        let min_y = if !mario.is_super_mario { -78.0 } else { -70.0 };
-        let max_y = -10.0;
 
-        if keyboard_input.pressed(KeyCode::Left) {
-            animation_timer.tick(time.delta());
-            direction_x -= 1.2;
+        if keyboard_input.pressed(KeyCode::Left) && !is_crouching {
+            direction_x -= 1.0;
             texture_atlas_sprite.flip_x = true;
-
-            if animation_timer.just_finished() && !keyboard_input.pressed(KeyCode::Up) {
-                texture_atlas_sprite.index = if texture_atlas_sprite.index == animation_indices.last
-                {
-                    animation_indices.first
-                } else {
-                    texture_atlas_sprite.index + 1
-                };
-            }
         }
 
-        if keyboard_input.pressed(KeyCode::Right) {
-            animation_timer.tick(time.delta());
-            direction_x += 1.2;
+        if keyboard_input.pressed(KeyCode::Right) && !is_crouching {
+            direction_x += 1.0;
             texture_atlas_sprite.flip_x = false;
+        }
 
-            if animation_timer.just_finished() && !keyboard_input.pressed(KeyCode::Up) {
-                texture_atlas_sprite.index = if texture_atlas_sprite.index == animation_indices.last
-                {
-                    animation_indices.first
-                } else {
-                    texture_atlas_sprite.index + 1
-                };
-            }
+        let is_running = keyboard_input.pressed(KeyCode::X);
+        let max_speed = if is_running {
+            physics.run_speed
+        } else {
+            physics.walk_speed
+        };
+        let target_speed = direction_x * max_speed;
+        let acceleration = if velocity.x * direction_x < 0.0 {
+            physics.skid_deceleration
+        } else if direction_x == 0.0 || velocity.x.abs() > max_speed {
+            physics.deceleration
+        } else if is_running {
+            physics.run_acceleration
+        } else {
+            physics.walk_acceleration
+        };
+        velocity.x = approach(velocity.x, target_speed, acceleration * delta_seconds);
+
+        let size = mario.power.size();
+        let is_on_vine = vine_query.iter().any(|(vine_transform, vine_collider)| {
+            collide(transform.translation, size, vine_transform.translation, vine_collider.size).is_some()
+        });
+
+        if keyboard_input.just_pressed(KeyCode::Up) && mario.is_on_ground && !is_on_vine {
+            let mario_jump_audio = asset_server.load(mario.power.jump_sound());
+
+            audio.play(mario_jump_audio);
+            velocity.y = physics.jump_speed;
+            mario.is_jumping = true;
         }
 
-        if keyboard_input.pressed(KeyCode::Up) {
-            animation_timer.tick(Duration::from_secs_f32(1.0));
-
-            if animation_timer.just_finished() {
-                if transform.translation.y == min_y {
-                    let mario_jump_audio = if !mario.is_super_mario {
-                        asset_server.load("mario_jump.ogg")
-                    } else {
-                        asset_server.load("super_mario_jump.ogg")
-                    };
-
-                    audio.play(mario_jump_audio);
-                }
-                if !mario.dont_go_up_until_settle {
-                    direction_y += 1.5;
-                } else {
-                    direction_y -= 1.5;
-                }
-            }
+        if !keyboard_input.pressed(KeyCode::Up) || velocity.y <= 0.0 {
+            mario.is_jumping = false;
+        }
+
+        if is_on_vine {
+            // Mario holds on to a vine instead of falling; Up and Down climb it.
+            mario.is_jumping = false;
+            velocity.y = if keyboard_input.pressed(KeyCode::Up) {
+                physics.climb_speed
+            } else if keyboard_input.pressed(KeyCode::Down) {
+                -physics.climb_speed
+            } else {
+                0.0
+            };
         } else {
-            direction_y -= 1.5;
+            let gravity = if mario.is_jumping {
+                physics.jump_gravity
+            } else {
+                physics.gravity
+            };
+            velocity.y = (velocity.y - gravity * delta_seconds).max(-physics.max_fall_speed);
         }
 
-        let mario_position_x = transform.translation.x + direction_x;
-        let mario_position_y = transform.translation.y + direction_y;
+        let collision = tilemap.move_and_collide(
+            &mut transform.translation,
+            size,
+            velocity.0 * delta_seconds,
+        );
+
+        if collision.left || collision.right {
+            velocity.x = 0.0;
+        }
+        if collision.top || collision.bottom {
+            velocity.y = 0.0;
+            mario.is_jumping = false;
+        }
+        mario.is_on_ground = collision.bottom;
 
-        transform.translation.x = mario_position_x.clamp(-250.0, 180.0);
-        transform.translation.y = mario_position_y.clamp(min_y, max_y);
+        *mario_animation = if !mario.is_on_ground {
+            MarioAnimation::Jump
+        } else if is_crouching {
+            MarioAnimation::Crouch
+        } else if velocity.x * direction_x < 0.0 {
+            MarioAnimation::Skid
+        } else if velocity.x == 0.0 {
+            MarioAnimation::Idle
+        } else if velocity.x.abs() > physics.walk_speed {
+            MarioAnimation::Run
+        } else {
+            MarioAnimation::Walk
+        };
 
-        if transform.translation.y == max_y {
-            mario.dont_go_up_until_settle = true;
-        } else if transform.translation.y == min_y {
-            mario.dont_go_up_until_settle = false;
+        if collision.top {
+            mario_head_bump_event_writer.send(MarioHeadBumpEvent {
+                x: transform.translation.x,
+                y: transform.translation.y + size.y / 2.0,
+            });
         }
 
-        if direction_x != 0.0 {
-            mario_move_event_writer.send(MarioMoveEvent {
+        if transform.translation.y + size.y / 2.0 < tilemap.bottom() {
+            mario_dead_event_writer.send(MarioDeadEvent {
                 x: transform.translation.x,
                 y: transform.translation.y,
             });
@@ -134,144 +186,186 @@ pub fn move_mario(
     }
 }
 
+pub fn animate_mario(mut mario_query: Query<(&MarioAnimation, &Mario, &mut Animation)>) {
+    for (mario_animation, mario, mut animation) in mario_query.iter_mut() {
+        animation.play(mario.power.clip(*mario_animation));
+    }
+}
+
 pub fn handle_mario_dead_event(
-    mut mario_query: Query<(&mut Transform, &mut AnimationTimer, &mut MarioDead), With<MarioDead>>,
+    time: Res<Time>,
+    physics: Res<PhysicsConfig>,
+    mut game_state: ResMut<State<GameState>>,
+    mut lives: ResMut<Lives>,
+    mut mario_query: Query<(&mut Transform, &mut Velocity, &mut MarioDead), With<MarioDead>>,
 ) {
     if mario_query.is_empty() {
         return;
     }
 
-    for (mut transform, mut animation_timer, mut mario) in mario_query.iter_mut() {
-        animation_timer.tick(Duration::from_secs_f32(1.0));
-        if animation_timer.just_finished() {
-            if mario.go_up {
-                transform.translation.y += 2.0;
-                if transform.translation.y >= -30.0 {
-                    mario.go_up = false;
-                }
+    let delta_seconds = time.delta_seconds();
+
+    for (mut transform, mut velocity, mut mario) in mario_query.iter_mut() {
+        velocity.y = (velocity.y - physics.gravity * delta_seconds).max(-physics.max_fall_speed);
+        transform.translation.y += velocity.y * delta_seconds;
+
+        if mario.timer.tick(time.delta()).just_finished() {
+            if **lives > 1 {
+                **lives -= 1;
+                let _ = game_state.restart();
             } else {
-                transform.translation.y -= 1.5;
+                **lives = 0;
+                let _ = game_state.set(GameState::GameOver);
             }
         }
     }
 }
 
 pub fn get_dead_mario_bundle(
-    asset_server: Res<AssetServer>,
-    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    asset_server: &AssetServer,
+    texture_atlases: &mut Assets<TextureAtlas>,
     x: f32,
     y: f32,
-) -> (bevy::prelude::SpriteSheetBundle, AnimationTimer, MarioDead) {
-    let mario_texture_handle = asset_server.load("mario_dead.png");
-
-    let mario_texture_atlas = TextureAtlas::from_grid(
-        mario_texture_handle,
-        Vec2::new(15.0, 16.0),
-        1,
-        1,
-        None,
-        None,
-    );
-
-    return (
+    jump_speed: f32,
+) -> (bevy::prelude::SpriteSheetBundle, Animation, Velocity, MarioDead) {
+    let clip = MarioPowerState::Small.clip(MarioAnimation::Die);
+
+    (
         SpriteSheetBundle {
-            texture_atlas: texture_atlases.add(mario_texture_atlas),
+            texture_atlas: texture_atlases.add(clip.sheet.texture_atlas(asset_server)),
             sprite: TextureAtlasSprite::new(0),
             transform: Transform::from_xyz(x, y, 2.0),
             ..default()
         },
-        AnimationTimer(Timer::from_seconds(0.1, TimerMode::Repeating)),
-        MarioDead { go_up: true },
-    );
+        Animation::new(clip),
+        Velocity(Vec2::new(0.0, jump_speed)),
+        MarioDead {
+            timer: Timer::from_seconds(MARIO_DEAD_DURATION, TimerMode::Once),
+        },
+    )
 }
 
 pub fn get_mario_bundle(
-    asset_server: Res<AssetServer>,
-    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
-    is_super_mario: bool,
+    asset_server: &AssetServer,
+    texture_atlases: &mut Assets<TextureAtlas>,
+    power: MarioPowerState,
     x: f32,
     y: f32,
 ) -> (
     bevy::prelude::SpriteSheetBundle,
-    AnimationIndices,
-    AnimationTimer,
+    Animation,
+    MarioAnimation,
+    Velocity,
+    Collider,
     Mario,
 ) {
-    let mario_texture_handle = if !is_super_mario {
-        asset_server.load("mario-walk.png")
-    } else {
-        asset_server.load("super-mario-walk.png")
-    };
-    let mario_texture_atlas = TextureAtlas::from_grid(
-        mario_texture_handle,
-        if !is_super_mario {
-            Vec2::new(15.0, 16.0)
-        } else {
-            Vec2::new(16.0, 32.0)
-        },
-        3,
-        1,
-        None,
-        None,
-    );
+    let clip = power.clip(MarioAnimation::Idle);
 
-    return (
+    (
         SpriteSheetBundle {
-            texture_atlas: texture_atlases.add(mario_texture_atlas),
+            texture_atlas: texture_atlases.add(clip.sheet.texture_atlas(asset_server)),
             sprite: TextureAtlasSprite::new(0),
             transform: Transform::from_xyz(x, y, 2.0),
             ..default()
         },
-        AnimationIndices { first: 0, last: 2 },
-        AnimationTimer(Timer::from_seconds(0.1, TimerMode::Repeating)),
+        Animation::new(clip),
+        MarioAnimation::Idle,
+        Velocity::default(),
+        Collider::new(power.size()),
         Mario {
-            is_super_mario,
-            dont_go_up_until_settle: false,
+            power,
+            is_on_ground: false,
+            is_jumping: false,
         },
-    );
+    )
 }
 
-pub fn mario_changed_as_supermario_event_read(
+#[allow(clippy::too_many_arguments)]
+pub fn mario_dead_event_read(
     mut commands: Commands,
+    mario_query: Query<Entity, With<Mario>>,
+    mut mario_dead_event_reader: EventReader<MarioDeadEvent>,
     asset_server: Res<AssetServer>,
-    mario_query: Query<(Entity, &mut Transform), With<Mario>>,
-    mut mario_changed_event_reader: EventReader<MarioChangedAsSuperMarioEvent>,
-    texture_atlases: ResMut<Assets<TextureAtlas>>,
+    audio: Res<Audio>,
+    audio_sinks: Res<Assets<AudioSink>>,
+    music_controller: Res<MarioLevelMusicController>,
+    mut star_music_controller: ResMut<StarMusicController>,
+    physics: Res<PhysicsConfig>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
 ) {
-    if mario_query.is_empty() || mario_changed_event_reader.is_empty() {
+    if mario_query.is_empty() || mario_dead_event_reader.is_empty() {
         return;
     }
 
-    let mario = mario_query.single();
-    if let Some(event) = mario_changed_event_reader.iter().next() {
-        let x = event.x;
-        let y = event.y + 7.0;
-        commands.entity(mario.0).despawn();
-        commands.spawn(get_mario_bundle(asset_server, texture_atlases, true, x, y));
+    if let Some(event) = mario_dead_event_reader.iter().last() {
+        if let Some(sink) = audio_sinks.get(&music_controller.0) {
+            sink.stop();
+        }
+        star_music_controller.stop(&audio_sinks);
+
+        let mariodie = asset_server.load("mariodie.ogg");
+        audio.play(mariodie);
+        commands.entity(mario_query.single()).despawn();
+        commands.spawn((
+            get_dead_mario_bundle(
+                &asset_server,
+                &mut texture_atlases,
+                event.x,
+                event.y,
+                physics.death_jump_speed,
+            ),
+            LevelScoped,
+        ));
     }
 }
 
-pub fn mario_move_event_read(
-    mut mario_move_event_reader: EventReader<MarioMoveEvent>,
-    mut magic_mushroom_query: Query<(&mut Visibility, &Transform), With<MagicMushroom>>,
-    mut mario_changed_event_writer: EventWriter<MarioChangedAsSuperMarioEvent>,
+#[allow(clippy::too_many_arguments)]
+pub fn mario_item_collision_event_read(
+    mut commands: Commands,
+    mut mario_item_collision_event_reader: EventReader<MarioItemCollisionEvent>,
+    mario_query: Query<&Transform, With<Mario>>,
+    magic_mushroom_query: Query<(&Transform, &MagicMushroom)>,
+    fire_flower_query: Query<&Transform, With<FireFlower>>,
+    mut mario_power_event_writer: EventWriter<MarioPowerEvent>,
+    mut score_event_writer: EventWriter<ScoreEvent>,
+    mut lives: ResMut<Lives>,
     asset_server: Res<AssetServer>,
     audio: Res<Audio>,
 ) {
-    for event in mario_move_event_reader.iter() {
-        for (mut visibility, transform) in magic_mushroom_query.iter_mut() {
-            if visibility.is_visible
-                && event.x >= transform.translation.x - 10.0
-                && event.x <= transform.translation.x + 10.0
-            {
-                let powerup = asset_server.load("powerup.ogg");
-                audio.play(powerup);
-                visibility.is_visible = false;
-                mario_changed_event_writer.send(MarioChangedAsSuperMarioEvent {
-                    x: event.x,
-                    y: event.y,
-                });
+    if mario_query.is_empty() {
+        return;
+    }
+
+    for event in mario_item_collision_event_reader.iter() {
+        if let Ok(transform) = fire_flower_query.get(event.item) {
+            commands.entity(event.item).despawn();
+            mario_power_event_writer.send(MarioPowerEvent {
+                change: PowerChange::FireFlower,
+            });
+            score_event_writer.send(ScoreEvent {
+                points: POWER_UP_POINTS,
+                x: transform.translation.x,
+                y: transform.translation.y,
+            });
+        }
+
+        if let Ok((transform, magic_mushroom)) = magic_mushroom_query.get(event.item) {
+            commands.entity(event.item).despawn();
+
+            if magic_mushroom.kind == MushroomKind::OneUp {
+                audio.play(asset_server.load("powerup.ogg"));
+                **lives += 1;
+                continue;
             }
+
+            mario_power_event_writer.send(MarioPowerEvent {
+                change: PowerChange::Mushroom,
+            });
+            score_event_writer.send(ScoreEvent {
+                points: POWER_UP_POINTS,
+                x: transform.translation.x,
+                y: transform.translation.y,
+            });
         }
     }
 }
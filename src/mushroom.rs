@@ -1,84 +1,146 @@
-use std::time::Duration;
+use bevy::{prelude::{Query, Transform, Component, Entity, EventReader, AssetServer, Assets, Vec2, Res, ResMut, Commands, default}, time::Time, sprite::{TextureAtlas, SpriteSheetBundle}};
 
-use bevy::prelude::{Query, Transform, Visibility, With, Component, EventReader};
+use crate::{collision::Collider, fire_flower::get_fire_flower_bundle, game_state::LevelScoped, mario::Mario, physics::{PhysicsConfig, Velocity}, tilemap::TileMap};
 
-use crate::animation::AnimationTimer;
+const MAGIC_MUSHROOM_SIZE: Vec2 = Vec2::new(16.0, 16.0);
+const MAGIC_MUSHROOM_SPEED: f32 = 40.0;
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MushroomKind {
+    /// Makes small Mario super.
+    Super,
+    /// Gives Mario an extra life.
+    OneUp,
+}
+
+impl MushroomKind {
+    fn texture(&self) -> &'static str {
+        match self {
+            MushroomKind::Super => "magicMushroom.png",
+            MushroomKind::OneUp => "1up-mushroom.png",
+        }
+    }
+}
+
+/// Rises out of its block, then slides along the ground, turning around at
+/// walls and falling off ledges like an enemy.
 #[derive(Component)]
 pub struct MagicMushroom {
     pub is_released: bool,
-    pub x_reached_max: bool,
+    pub go_left: bool,
+    pub kind: MushroomKind,
 }
 
+/// `x` and `y` are the centre of the question block the mushroom comes out
+/// of, which may be gone by the time the event is read.
 pub struct MagicMushroomReleaseEvent {
     pub x: f32,
     pub y: f32,
+    pub kind: MushroomKind,
+}
+
+pub fn get_magic_mushroom_bundle(
+    asset_server: &AssetServer,
+    texture_atlases: &mut Assets<TextureAtlas>,
+    kind: MushroomKind,
+    x: f32,
+    y: f32,
+) -> (SpriteSheetBundle, Velocity, Collider, MagicMushroom) {
+    let magicmushroom_texture_atlas = TextureAtlas::from_grid(
+        asset_server.load(kind.texture()),
+        MAGIC_MUSHROOM_SIZE,
+        1,
+        1,
+        None,
+        None,
+    );
+
+    (
+        SpriteSheetBundle {
+            texture_atlas: texture_atlases.add(magicmushroom_texture_atlas),
+            transform: Transform::from_xyz(x, y, 2.5),
+            ..default()
+        },
+        Velocity::default(),
+        Collider::new(MAGIC_MUSHROOM_SIZE),
+        MagicMushroom {
+            is_released: false,
+            go_left: false,
+            kind,
+        },
+    )
 }
 
 pub fn move_mushroom(
-    mut query: Query<
-        (
-            &mut AnimationTimer,
-            &mut Transform,
-            &mut Visibility,
-            &mut MagicMushroom,
-        ),
-        With<MagicMushroom>,
-    >,
+    mut commands: Commands,
+    time: Res<Time>,
+    physics: Res<PhysicsConfig>,
+    tilemap: Res<TileMap>,
+    mut query: Query<(Entity, &mut Transform, &mut Velocity, &mut MagicMushroom)>,
 ) {
-    for (mut timer, mut transform, mut visibility, mut magic_mushroom) in &mut query.iter_mut() {
-        if visibility.is_visible {
-            timer.tick(Duration::from_secs_f32(1.0));
-            if timer.just_finished() {
-                if !magic_mushroom.is_released {
-                    transform.translation.y += 0.4;
-                    if transform.translation.y >= -11.0 {
-                        magic_mushroom.is_released = true;
-                    }
-                } else {
-                    let mut direction_x = 0.5;
-                    let mut direction_y = 0.1;
-
-                    if transform.translation.x >= 12.0 {
-                        direction_y = 0.5;
-                    }
-
-                    if magic_mushroom.x_reached_max {
-                        direction_x = -0.5;
-                    }
-
-                    let mushroom_position_x = transform.translation.x + direction_x;
-                    let mushroom_position_y = transform.translation.y - direction_y;
-
-                    transform.translation.x = mushroom_position_x.clamp(-280.0, 180.0);
-                    transform.translation.y = mushroom_position_y.clamp(-78.0, -12.0);
-
-                    if transform.translation.x == 180.0 {
-                        magic_mushroom.x_reached_max = true;
-                    }
-
-                    if transform.translation.x == -280.0 {
-                        visibility.is_visible = false;
-                    }
-                }
+    let delta_seconds = time.delta_seconds();
+
+    for (entity, mut transform, mut velocity, mut magic_mushroom) in query.iter_mut() {
+        if !magic_mushroom.is_released {
+            transform.translation.y += physics.item_rise_speed * delta_seconds;
+            if !tilemap.overlaps_solid(transform.translation, MAGIC_MUSHROOM_SIZE) {
+                magic_mushroom.is_released = true;
             }
+            continue;
+        }
+
+        velocity.x = if magic_mushroom.go_left { -MAGIC_MUSHROOM_SPEED } else { MAGIC_MUSHROOM_SPEED };
+        velocity.y = (velocity.y - physics.gravity * delta_seconds).max(-physics.max_fall_speed);
+
+        let collision = tilemap.move_and_collide(
+            &mut transform.translation,
+            MAGIC_MUSHROOM_SIZE,
+            velocity.0 * delta_seconds,
+        );
+
+        if collision.bottom || collision.top {
+            velocity.y = 0.0;
+        }
+
+        if collision.right {
+            magic_mushroom.go_left = true;
+        } else if collision.left {
+            magic_mushroom.go_left = false;
+        }
+
+        if transform.translation.y + MAGIC_MUSHROOM_SIZE.y / 2.0 < tilemap.bottom() {
+            commands.entity(entity).despawn();
         }
     }
 }
 
 pub fn magic_mushroom_event_read(
+    mut commands: Commands,
     mut magic_mushroom_event_reader: EventReader<MagicMushroomReleaseEvent>,
-    mut magic_mushroom_query: Query<(&mut Visibility, &Transform), With<MagicMushroom>>,
+    mario_query: Query<&Mario>,
+    asset_server: Res<AssetServer>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
 ) {
+    let is_big = mario_query.iter().any(|mario| mario.power.is_big());
+
     for event in magic_mushroom_event_reader.iter() {
-        for (mut magic_mushroom_visibility, magic_mushroom_transform) in
-            magic_mushroom_query.iter_mut()
-        {
-            if event.x == magic_mushroom_transform.translation.x
-                && event.y == magic_mushroom_transform.translation.y
-            {
-                magic_mushroom_visibility.is_visible = true;
-            }
+        if is_big && event.kind == MushroomKind::Super {
+            commands.spawn((
+                get_fire_flower_bundle(&asset_server, &mut texture_atlases, event.x, event.y),
+                LevelScoped,
+            ));
+            continue;
         }
+
+        commands.spawn((
+            get_magic_mushroom_bundle(
+                &asset_server,
+                &mut texture_atlases,
+                event.kind,
+                event.x,
+                event.y,
+            ),
+            LevelScoped,
+        ));
     }
 }
@@ -0,0 +1,60 @@
+use bevy::prelude::{Component, Deref, DerefMut, Resource, Vec2};
+
+/// Speeds are in world units per second and accelerations in units per second
+/// squared, so everything that uses them has to scale by `Time::delta_seconds`.
+#[derive(Resource)]
+pub struct PhysicsConfig {
+    pub gravity: f32,
+    /// Gravity while the jump button is held on the way up; lower than
+    /// `gravity`, so holding the button longer jumps higher.
+    pub jump_gravity: f32,
+    pub max_fall_speed: f32,
+    /// Gravity on things that fly through the air without landing on their
+    /// feet, like fireballs, hammers, stars, brick debris and knocked-out
+    /// enemies; lower than `gravity` for a floatier arc.
+    pub projectile_gravity: f32,
+    pub jump_speed: f32,
+    pub walk_speed: f32,
+    pub run_speed: f32,
+    pub walk_acceleration: f32,
+    pub run_acceleration: f32,
+    pub deceleration: f32,
+    pub skid_deceleration: f32,
+    pub death_jump_speed: f32,
+    pub climb_speed: f32,
+    /// How fast an item rises out of the block it comes from.
+    pub item_rise_speed: f32,
+}
+
+impl Default for PhysicsConfig {
+    fn default() -> Self {
+        PhysicsConfig {
+            gravity: 1400.0,
+            jump_gravity: 500.0,
+            max_fall_speed: 270.0,
+            projectile_gravity: 900.0,
+            jump_speed: 260.0,
+            walk_speed: 72.0,
+            run_speed: 120.0,
+            walk_acceleration: 200.0,
+            run_acceleration: 280.0,
+            deceleration: 300.0,
+            skid_deceleration: 500.0,
+            death_jump_speed: 300.0,
+            climb_speed: 60.0,
+            item_rise_speed: 24.0,
+        }
+    }
+}
+
+#[derive(Component, Default, Deref, DerefMut)]
+pub struct Velocity(pub Vec2);
+
+/// Moves `current` towards `target` by at most `max_delta` without overshooting.
+pub fn approach(current: f32, target: f32, max_delta: f32) -> f32 {
+    if current < target {
+        (current + max_delta).min(target)
+    } else {
+        (current - max_delta).max(target)
+    }
+}
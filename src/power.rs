@@ -0,0 +1,311 @@
+use bevy::{
+    prelude::{AssetServer, Assets, Audio, Commands, Component, Entity, EventReader, EventWriter, Handle, Query, Res, ResMut, State, Transform, Vec2, Visibility},
+    sprite::TextureAtlas,
+    time::{Time, Timer, TimerMode},
+};
+
+use crate::{
+    animation::{AnimationClip, AnimationMode, SpriteSheet},
+    collision::Collider,
+    game_state::GameState,
+    mario::{Mario, MarioAnimation, MarioDeadEvent},
+};
+
+/// How long Mario ignores enemies after being hurt.
+const INVULNERABLE_DURATION: f32 = 2.0;
+const INVULNERABLE_BLINK: f32 = 0.05;
+const POWER_TRANSITION_DURATION: f32 = 1.0;
+const POWER_TRANSITION_FLICKER: f32 = 0.1;
+const MARIO_WALK_FRAME_DURATION: f32 = 0.1;
+const MARIO_RUN_FRAME_DURATION: f32 = 0.05;
+
+/// What Mario can currently do. Being invulnerable is not a power state of its
+/// own but an `Invulnerable` overlay on top of whichever state he is in.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MarioPowerState {
+    Small,
+    Super,
+    Fire,
+}
+
+impl MarioPowerState {
+    pub fn is_big(&self) -> bool {
+        *self != MarioPowerState::Small
+    }
+
+    /// Hitbox, which is a little narrower than the sprite.
+    pub fn size(&self) -> Vec2 {
+        if self.is_big() {
+            Vec2::new(14.0, 32.0)
+        } else {
+            Vec2::new(14.0, 16.0)
+        }
+    }
+
+    /// How Mario looks doing `animation` in this state. Small Mario has no
+    /// crouch of his own, and he always dies small.
+    pub fn clip(&self, animation: MarioAnimation) -> AnimationClip {
+        let small = Vec2::new(16.0, 16.0);
+        let big = Vec2::new(16.0, 32.0);
+        let walk_sheet = match self {
+            MarioPowerState::Small => SpriteSheet {
+                texture: "mario-walk.png",
+                frame_size: Vec2::new(15.0, 16.0),
+                columns: 3,
+            },
+            MarioPowerState::Super => SpriteSheet {
+                texture: "super-mario-walk.png",
+                frame_size: big,
+                columns: 3,
+            },
+            MarioPowerState::Fire => SpriteSheet {
+                texture: "fire-mario-walk.png",
+                frame_size: big,
+                columns: 3,
+            },
+        };
+        let walk = |name, frame_duration| AnimationClip {
+            name,
+            sheet: walk_sheet,
+            first: 0,
+            last: 2,
+            frame_duration,
+            mode: AnimationMode::Loop,
+        };
+        let pose = |name, texture, frame_size| {
+            AnimationClip::still(name, SpriteSheet { texture, frame_size, columns: 1 }, 0)
+        };
+
+        match (self, animation) {
+            (_, MarioAnimation::Walk) => walk("walk", MARIO_WALK_FRAME_DURATION),
+            (_, MarioAnimation::Run) => walk("run", MARIO_RUN_FRAME_DURATION),
+            (_, MarioAnimation::Die) => pose("die", "mario_dead.png", Vec2::new(15.0, 16.0)),
+            (MarioPowerState::Small, MarioAnimation::Idle | MarioAnimation::Crouch) => {
+                pose("idle", "mario-stand.png", Vec2::new(12.0, 16.0))
+            }
+            (MarioPowerState::Small, MarioAnimation::Jump) => pose("jump", "mario-jump.png", small),
+            (MarioPowerState::Small, MarioAnimation::Skid) => pose("skid", "mario-skid.png", small),
+            (MarioPowerState::Super, MarioAnimation::Idle) => pose("idle", "supermario-stand.png", big),
+            (MarioPowerState::Super, MarioAnimation::Jump) => pose("jump", "supermario-jump.png", big),
+            (MarioPowerState::Super, MarioAnimation::Skid) => pose("skid", "supermario-skid.png", big),
+            (MarioPowerState::Super, MarioAnimation::Crouch) => pose("crouch", "supermario-crouch.png", big),
+            (MarioPowerState::Fire, MarioAnimation::Idle) => pose("idle", "firemario-stand.png", big),
+            (MarioPowerState::Fire, MarioAnimation::Jump) => pose("jump", "firemario-jump.png", big),
+            (MarioPowerState::Fire, MarioAnimation::Skid) => pose("skid", "firemario-skid.png", big),
+            (MarioPowerState::Fire, MarioAnimation::Crouch) => pose("crouch", "firemario-crouch.png", big),
+        }
+    }
+
+    pub fn jump_sound(&self) -> &'static str {
+        if self.is_big() {
+            "super_mario_jump.ogg"
+        } else {
+            "mario_jump.ogg"
+        }
+    }
+
+    /// The state Mario ends up in after `change`, or `None` if it kills him.
+    pub fn after(&self, change: PowerChange) -> Option<MarioPowerState> {
+        match (change, self) {
+            (PowerChange::Mushroom, MarioPowerState::Small) => Some(MarioPowerState::Super),
+            (PowerChange::Mushroom, _) => Some(*self),
+            (PowerChange::FireFlower, _) => Some(MarioPowerState::Fire),
+            (PowerChange::Damage, MarioPowerState::Small) => None,
+            (PowerChange::Damage, _) => Some(MarioPowerState::Small),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PowerChange {
+    Mushroom,
+    FireFlower,
+    Damage,
+}
+
+/// The one way to grow, shrink or hurt Mario.
+pub struct MarioPowerEvent {
+    pub change: PowerChange,
+}
+
+#[derive(Component)]
+pub struct PowerTransition {
+    from: MarioPowerState,
+    to: MarioPowerState,
+    from_texture_atlas: Handle<TextureAtlas>,
+    to_texture_atlas: Handle<TextureAtlas>,
+    /// Mario grows and shrinks from his feet, which stay put.
+    feet_y: f32,
+    is_damage: bool,
+    flicker: Timer,
+    timer: Timer,
+}
+
+/// Given to Mario after he is hurt. While present he blinks and enemies that
+/// would hurt him pass through him instead, though he can still stomp and
+/// kick them; it removes itself once `timer` is done.
+#[derive(Component)]
+pub struct Invulnerable {
+    timer: Timer,
+    blink: Timer,
+}
+
+impl Default for Invulnerable {
+    fn default() -> Self {
+        Invulnerable {
+            timer: Timer::from_seconds(INVULNERABLE_DURATION, TimerMode::Once),
+            blink: Timer::from_seconds(INVULNERABLE_BLINK, TimerMode::Repeating),
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn change_mario_power(
+    mut commands: Commands,
+    mut mario_power_event_reader: EventReader<MarioPowerEvent>,
+    mut mario_dead_event_writer: EventWriter<MarioDeadEvent>,
+    mut mario_query: Query<(
+        Entity,
+        &mut Transform,
+        &mut Handle<TextureAtlas>,
+        &mut Collider,
+        &mut Mario,
+        &MarioAnimation,
+        Option<&Invulnerable>,
+    )>,
+    mut game_state: ResMut<State<GameState>>,
+    asset_server: Res<AssetServer>,
+    audio: Res<Audio>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+) {
+    let (entity, mut transform, mut texture_atlas, mut collider, mut mario, animation, invulnerable) =
+        match mario_query.get_single_mut() {
+            Ok(mario) => mario,
+            Err(_) => return,
+        };
+
+    // The game freezes for the transition, so only the first change of a
+    // frame is applied.
+    let change = match mario_power_event_reader
+        .iter()
+        .map(|event| event.change)
+        .find(|change| *change != PowerChange::Damage || invulnerable.is_none())
+    {
+        Some(change) => change,
+        None => return,
+    };
+
+    let power = match mario.power.after(change) {
+        Some(power) => power,
+        None => {
+            mario_dead_event_writer.send(MarioDeadEvent {
+                x: transform.translation.x,
+                y: transform.translation.y,
+            });
+            return;
+        }
+    };
+
+    let sound = if change == PowerChange::Damage { "powerdown.ogg" } else { "powerup.ogg" };
+    audio.play(asset_server.load(sound));
+
+    if power == mario.power {
+        return;
+    }
+
+    let to_texture_atlas = texture_atlases.add(power.clip(*animation).sheet.texture_atlas(&asset_server));
+    let feet_y = transform.translation.y - mario.power.size().y / 2.0;
+    let is_damage = change == PowerChange::Damage;
+
+    // Another state change is already queued this frame, so there is no
+    // freeze to play the transition in; change Mario straight away rather
+    // than lose the power-up or the hit.
+    if game_state.push(GameState::Transforming).is_err() {
+        *texture_atlas = to_texture_atlas;
+        transform.translation.y = feet_y + power.size().y / 2.0;
+        mario.power = power;
+        collider.size = power.size();
+        if is_damage {
+            commands.entity(entity).insert(Invulnerable::default());
+        }
+        return;
+    }
+
+    commands.entity(entity).insert(PowerTransition {
+        from: mario.power,
+        to: power,
+        from_texture_atlas: texture_atlas.clone(),
+        to_texture_atlas,
+        feet_y,
+        is_damage,
+        flicker: Timer::from_seconds(POWER_TRANSITION_FLICKER, TimerMode::Repeating),
+        timer: Timer::from_seconds(POWER_TRANSITION_DURATION, TimerMode::Once),
+    });
+}
+
+/// Flickers Mario between his old and new look while everything else is
+/// frozen, then settles him into the new power state.
+#[allow(clippy::type_complexity)]
+pub fn animate_power_transition(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut game_state: ResMut<State<GameState>>,
+    mut mario_query: Query<(
+        Entity,
+        &mut Transform,
+        &mut Handle<TextureAtlas>,
+        &mut Collider,
+        &mut Mario,
+        &mut PowerTransition,
+    )>,
+) {
+    for (entity, mut transform, mut texture_atlas, mut collider, mut mario, mut transition) in
+        mario_query.iter_mut()
+    {
+        let is_finished = transition.timer.tick(time.delta()).finished();
+
+        let shown = if is_finished {
+            transition.to
+        } else if transition.flicker.tick(time.delta()).just_finished() {
+            if *texture_atlas == transition.to_texture_atlas {
+                transition.from
+            } else {
+                transition.to
+            }
+        } else {
+            continue;
+        };
+
+        *texture_atlas = if shown == transition.to {
+            transition.to_texture_atlas.clone()
+        } else {
+            transition.from_texture_atlas.clone()
+        };
+        transform.translation.y = transition.feet_y + shown.size().y / 2.0;
+
+        if is_finished {
+            mario.power = transition.to;
+            collider.size = transition.to.size();
+            if transition.is_damage {
+                commands.entity(entity).insert(Invulnerable::default());
+            }
+            commands.entity(entity).remove::<PowerTransition>();
+            let _ = game_state.pop();
+        }
+    }
+}
+
+pub fn tick_invulnerability(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut Visibility, &mut Invulnerable)>,
+) {
+    for (entity, mut visibility, mut invulnerable) in query.iter_mut() {
+        if invulnerable.timer.tick(time.delta()).finished() {
+            visibility.is_visible = true;
+            commands.entity(entity).remove::<Invulnerable>();
+        } else if invulnerable.blink.tick(time.delta()).just_finished() {
+            visibility.is_visible = !visibility.is_visible;
+        }
+    }
+}
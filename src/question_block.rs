@@ -1,95 +1,206 @@
-use bevy::{prelude::{Res, Component, Query, With, Commands, Transform, Entity, Visibility, EventWriter, AssetServer, Audio}, time::Time, sprite::TextureAtlasSprite};
+use bevy::{prelude::{Res, ResMut, Component, Query, Without, Commands, Transform, Entity, Visibility, EventWriter, AssetServer, Audio, Assets, Vec2, EventReader, default}, sprite::{TextureAtlasSprite, TextureAtlas, SpriteSheetBundle}};
 
-use crate::{AnimationTimer, animation::{AnimationIndices, animate}, mario::Mario, mushroom::MagicMushroomReleaseEvent};
+use crate::{coin::get_block_coin_bundle, game_state::LevelScoped, block_bump::{BlockBump, BlockBumpEvent}, animation::{Animation, AnimationClip, AnimationMode, SpriteSheet}, mario::MarioHeadBumpEvent, mushroom::{MagicMushroomReleaseEvent, MushroomKind}, fire_flower::FireFlowerReleaseEvent, score::CoinEvent, collision::Collider, level::BlockContents, star::StarReleaseEvent, tilemap::{Tile, TileMap}, vine::VineReleaseEvent};
 
+const QUESTION_BLOCK_SIZE: Vec2 = Vec2::new(16.0, 16.0);
+const MULTI_COIN_HITS: u32 = 10;
+const QUESTION_BLOCK_CLIP: AnimationClip = AnimationClip {
+    name: "shine",
+    sheet: SpriteSheet {
+        texture: "question-block.png",
+        frame_size: QUESTION_BLOCK_SIZE,
+        columns: 6,
+    },
+    first: 0,
+    last: 5,
+    frame_duration: 0.1,
+    mode: AnimationMode::Loop,
+};
+
+/// `hits_left` counts down to the hit that empties the block, which is the
+/// first one for everything except multi-coin blocks.
 #[derive(Component)]
 pub struct QuestionBlock {
-    pub is_mushroom: bool,
+    pub contents: BlockContents,
+    pub hits_left: u32,
 }
 
 #[derive(Component)]
 pub struct EmptyBlock;
 
-pub fn animate_question_blocks(
-    time: Res<Time>,
-    mut query: Query<
-        (
-            &mut AnimationTimer,
-            &mut TextureAtlasSprite,
-            &AnimationIndices,
-        ),
-        With<QuestionBlock>,
-    >,
-) {
-    for (mut timer, mut sprite, animation_indices) in query.iter_mut() {
-        animate(&time, &mut timer, &mut sprite, animation_indices);
-    }
+pub fn get_question_block_bundle(
+    asset_server: &AssetServer,
+    texture_atlases: &mut Assets<TextureAtlas>,
+    contents: BlockContents,
+    is_visible: bool,
+    x: f32,
+    y: f32,
+) -> (SpriteSheetBundle, Animation, Collider, QuestionBlock) {
+    (
+        SpriteSheetBundle {
+            visibility: Visibility { is_visible },
+            texture_atlas: texture_atlases.add(QUESTION_BLOCK_CLIP.sheet.texture_atlas(asset_server)),
+            sprite: TextureAtlasSprite::new(0),
+            transform: Transform::from_xyz(x, y, 3.0),
+            ..default()
+        },
+        Animation::new(QUESTION_BLOCK_CLIP),
+        Collider::new(QUESTION_BLOCK_SIZE),
+        QuestionBlock {
+            contents,
+            hits_left: if contents == BlockContents::MultiCoin { MULTI_COIN_HITS } else { 1 },
+        },
+    )
 }
 
+pub fn get_empty_block_bundle(
+    asset_server: &AssetServer,
+    texture_atlases: &mut Assets<TextureAtlas>,
+    x: f32,
+    y: f32,
+) -> (SpriteSheetBundle, EmptyBlock) {
+    let emptyblock_texture_atlas = TextureAtlas::from_grid(
+        asset_server.load("emptyBlock.png"),
+        Vec2::new(16.0, 16.0),
+        1,
+        1,
+        None,
+        None,
+    );
+
+    (
+        SpriteSheetBundle {
+            texture_atlas: texture_atlases.add(emptyblock_texture_atlas),
+            transform: Transform::from_xyz(x, y, 3.0),
+            ..default()
+        },
+        EmptyBlock,
+    )
+}
 
+#[allow(clippy::too_many_arguments)]
 pub fn hit_questionblock_by_mario(
     commands: Commands,
-    mario_query: Query<&Transform, With<Mario>>,
-    question_block_query: Query<(Entity, &Transform, &QuestionBlock), With<QuestionBlock>>,
-    empty_block_query: Query<(&mut Visibility, &Transform), With<EmptyBlock>>,
+    mut mario_head_bump_event_reader: EventReader<MarioHeadBumpEvent>,
+    question_block_query: Query<(Entity, &Transform, &mut Visibility, &Collider, &mut QuestionBlock), Without<BlockBump>>,
+    tilemap: ResMut<TileMap>,
     magic_mushroom_event_writer: EventWriter<MagicMushroomReleaseEvent>,
+    fire_flower_event_writer: EventWriter<FireFlowerReleaseEvent>,
+    star_release_event_writer: EventWriter<StarReleaseEvent>,
+    vine_release_event_writer: EventWriter<VineReleaseEvent>,
+    block_bump_event_writer: EventWriter<BlockBumpEvent>,
+    coin_event_writer: EventWriter<CoinEvent>,
     asset_server: Res<AssetServer>,
     audio: Res<Audio>,
+    texture_atlases: ResMut<Assets<TextureAtlas>>,
 ) {
-    if mario_query.is_empty() {
-        return;
+    if let Some(event) = mario_head_bump_event_reader.iter().last() {
+        handle_hit_questionblock(
+            commands,
+            event,
+            question_block_query,
+            tilemap,
+            magic_mushroom_event_writer,
+            fire_flower_event_writer,
+            star_release_event_writer,
+            vine_release_event_writer,
+            block_bump_event_writer,
+            coin_event_writer,
+            asset_server,
+            audio,
+            texture_atlases,
+        );
     }
-
-    let transform = mario_query.single();
-
-    handle_hit_questionblock(
-        commands,
-        transform,
-        question_block_query,
-        empty_block_query,
-        magic_mushroom_event_writer,
-        asset_server,
-        audio,
-    );
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn handle_hit_questionblock(
     mut commands: Commands,
-    mario_transform: &Transform,
-    question_block_query: Query<(Entity, &Transform, &QuestionBlock), With<QuestionBlock>>,
-    mut empty_block_query: Query<(&mut Visibility, &Transform), With<EmptyBlock>>,
+    head_bump: &MarioHeadBumpEvent,
+    mut question_block_query: Query<(Entity, &Transform, &mut Visibility, &Collider, &mut QuestionBlock), Without<BlockBump>>,
+    mut tilemap: ResMut<TileMap>,
     mut magic_mushroom_event_writer: EventWriter<MagicMushroomReleaseEvent>,
+    mut fire_flower_event_writer: EventWriter<FireFlowerReleaseEvent>,
+    mut star_release_event_writer: EventWriter<StarReleaseEvent>,
+    mut vine_release_event_writer: EventWriter<VineReleaseEvent>,
+    mut block_bump_event_writer: EventWriter<BlockBumpEvent>,
+    mut coin_event_writer: EventWriter<CoinEvent>,
     asset_server: Res<AssetServer>,
     audio: Res<Audio>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
 ) {
-    for (ent, question_block_transform, question_block) in question_block_query.iter() {
-        if (mario_transform.translation.x >= question_block_transform.translation.x - 5.0
-            && mario_transform.translation.x <= question_block_transform.translation.x + 5.0)
-            && (mario_transform.translation.y >= question_block_transform.translation.y - 5.0
-                && mario_transform.translation.y <= question_block_transform.translation.y + 5.0)
-        {
-            commands.entity(ent).despawn();
-
-            for (mut empty_block_visibility, empty_block_transform) in empty_block_query.iter_mut()
-            {
-                if question_block_transform.translation.x == empty_block_transform.translation.x
-                    && question_block_transform.translation.y == empty_block_transform.translation.y
-                {
-                    empty_block_visibility.is_visible = true;
-
-                    if question_block.is_mushroom {
-                        let mushroom_appears = asset_server.load("mushroom_appears.ogg");
-                        audio.play(mushroom_appears);
-                        magic_mushroom_event_writer.send(MagicMushroomReleaseEvent {
-                            x: empty_block_transform.translation.x,
-                            y: empty_block_transform.translation.y,
-                        });
+    // Mario's head stops flush against the block, so look just above it.
+    let head = Vec2::new(head_bump.x, head_bump.y + 1.0);
+
+    for (ent, question_block_transform, mut visibility, collider, mut question_block) in question_block_query.iter_mut() {
+        if !collider.contains(question_block_transform.translation, head) {
+            continue;
+        }
+
+        let x = question_block_transform.translation.x;
+        let y = question_block_transform.translation.y;
+
+        match question_block.contents {
+            BlockContents::Coin | BlockContents::MultiCoin => {
+                let coin_sound = asset_server.load("coin.ogg");
+                audio.play(coin_sound);
+                commands.spawn((
+                    get_block_coin_bundle(&asset_server, &mut texture_atlases, x, y),
+                    LevelScoped,
+                ));
+                coin_event_writer.send(CoinEvent {
+                    x,
+                    y: y + QUESTION_BLOCK_SIZE.y,
+                });
+            }
+            BlockContents::Mushroom | BlockContents::OneUp => {
+                let mushroom_appears = asset_server.load("mushroom_appears.ogg");
+                audio.play(mushroom_appears);
+                magic_mushroom_event_writer.send(MagicMushroomReleaseEvent {
+                    x,
+                    y,
+                    kind: if question_block.contents == BlockContents::OneUp {
+                        MushroomKind::OneUp
                     } else {
-                        let coin_sound = asset_server.load("coin.ogg");
-                        audio.play(coin_sound);
-                    }
-                }
+                        MushroomKind::Super
+                    },
+                });
+            }
+            BlockContents::FireFlower => {
+                let flower_appears = asset_server.load("mushroom_appears.ogg");
+                audio.play(flower_appears);
+                fire_flower_event_writer.send(FireFlowerReleaseEvent { x, y });
+            }
+            BlockContents::Star => {
+                let star_appears = asset_server.load("mushroom_appears.ogg");
+                audio.play(star_appears);
+                star_release_event_writer.send(StarReleaseEvent { x, y });
+            }
+            BlockContents::Vine => {
+                let vine_appears = asset_server.load("mushroom_appears.ogg");
+                audio.play(vine_appears);
+                vine_release_event_writer.send(VineReleaseEvent { x, y });
             }
         }
+
+        block_bump_event_writer.send(BlockBumpEvent { x, y });
+
+        // Hidden blocks only show up and become solid from every side once
+        // they are found.
+        visibility.is_visible = true;
+        tilemap.set_tile_at(x, y, Tile::Solid);
+
+        question_block.hits_left -= 1;
+        if question_block.hits_left > 0 {
+            commands.entity(ent).insert(BlockBump::new(y));
+            continue;
+        }
+
+        commands.entity(ent).despawn();
+        commands.spawn((
+            get_empty_block_bundle(&asset_server, &mut texture_atlases, x, y),
+            BlockBump::new(y),
+            LevelScoped,
+        ));
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,111 @@
+use bevy::{
+    prelude::{
+        AssetServer, Color, Commands, Component, Entity, EventReader, EventWriter, Query, Res,
+        ResMut, Resource, Text, Text2dBundle, TextAlignment, TextStyle, Transform, default,
+    },
+    time::{Time, Timer, TimerMode},
+};
+
+use crate::game_state::{LevelScoped, Lives, FONT};
+
+pub const STOMP_POINTS: u32 = 100;
+pub const BRICK_POINTS: u32 = 50;
+pub const COIN_POINTS: u32 = 200;
+pub const FIREBALL_POINTS: u32 = 200;
+pub const STAR_KILL_POINTS: u32 = 200;
+pub const POWER_UP_POINTS: u32 = 1000;
+
+const COINS_PER_EXTRA_LIFE: u32 = 100;
+const SCORE_POPUP_DURATION: f32 = 0.8;
+const SCORE_POPUP_SPEED: f32 = 30.0;
+
+/// Everything shown on the HUD. `time` is the level's time limit in seconds.
+#[derive(Resource, Default)]
+pub struct Score {
+    pub points: u32,
+    pub coins: u32,
+    pub time: u32,
+}
+
+/// Awards `points` and shows them floating up from where they were earned.
+pub struct ScoreEvent {
+    pub points: u32,
+    pub x: f32,
+    pub y: f32,
+}
+
+pub struct CoinEvent {
+    pub x: f32,
+    pub y: f32,
+}
+
+#[derive(Component)]
+pub struct ScorePopup {
+    timer: Timer,
+}
+
+pub fn collect_coins(
+    mut coin_event_reader: EventReader<CoinEvent>,
+    mut score_event_writer: EventWriter<ScoreEvent>,
+    mut score: ResMut<Score>,
+    mut lives: ResMut<Lives>,
+) {
+    for event in coin_event_reader.iter() {
+        score.coins += 1;
+        if score.coins == COINS_PER_EXTRA_LIFE {
+            score.coins = 0;
+            **lives += 1;
+        }
+
+        score_event_writer.send(ScoreEvent {
+            points: COIN_POINTS,
+            x: event.x,
+            y: event.y,
+        });
+    }
+}
+
+pub fn add_score(
+    mut commands: Commands,
+    mut score_event_reader: EventReader<ScoreEvent>,
+    mut score: ResMut<Score>,
+    asset_server: Res<AssetServer>,
+) {
+    for event in score_event_reader.iter() {
+        score.points += event.points;
+
+        commands.spawn((
+            Text2dBundle {
+                text: Text::from_section(
+                    event.points.to_string(),
+                    TextStyle {
+                        font: asset_server.load(FONT),
+                        font_size: 8.0,
+                        color: Color::WHITE,
+                    },
+                )
+                .with_alignment(TextAlignment::CENTER),
+                transform: Transform::from_xyz(event.x, event.y + 8.0, 10.0),
+                ..default()
+            },
+            ScorePopup {
+                timer: Timer::from_seconds(SCORE_POPUP_DURATION, TimerMode::Once),
+            },
+            LevelScoped,
+        ));
+    }
+}
+
+pub fn move_score_popups(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut Transform, &mut ScorePopup)>,
+) {
+    for (entity, mut transform, mut popup) in query.iter_mut() {
+        transform.translation.y += SCORE_POPUP_SPEED * time.delta_seconds();
+
+        if popup.timer.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
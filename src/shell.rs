@@ -0,0 +1,136 @@
+use bevy::{
+    prelude::{Commands, Component, Entity, EventReader, EventWriter, Query, Res, ResMut, Transform, Vec2, With},
+    time::{Time, Timer, TimerMode},
+};
+
+use crate::{
+    collision::EnemyCollisionEvent,
+    enemy::{knock_out_enemy, Enemy, EnemyDead},
+    game_state::Lives,
+    physics::{PhysicsConfig, Velocity},
+    score::ScoreEvent,
+    tilemap::TileMap,
+};
+
+pub const SHELL_SIZE: Vec2 = Vec2::new(16.0, 16.0);
+
+const SHELL_SPEED: f32 = 180.0;
+
+/// How long a shell ignores Mario after it is stomped or kicked, so he is not
+/// hurt by (or kicks again) the shell he is still standing in.
+const SHELL_GRACE_DURATION: f32 = 0.3;
+
+/// Points for each enemy a single kick of the shell knocks out, in order. Past
+/// the end of the chain every further enemy is worth an extra life instead.
+const SHELL_COMBO_POINTS: [u32; 7] = [500, 800, 1000, 2000, 4000, 5000, 8000];
+
+/// A turtle that has been stomped into its shell. The shell stays an `Enemy`
+/// so it keeps colliding with Mario, but it no longer counts towards clearing
+/// the stage. It slides with its `Velocity`, whose X is zero while it rests.
+#[derive(Component)]
+pub struct Shell {
+    pub grace: Timer,
+    pub combo: usize,
+}
+
+impl Default for Shell {
+    fn default() -> Self {
+        Shell {
+            grace: Timer::from_seconds(SHELL_GRACE_DURATION, TimerMode::Once),
+            combo: 0,
+        }
+    }
+}
+
+impl Shell {
+    pub fn is_sliding(velocity: &Velocity) -> bool {
+        velocity.x != 0.0
+    }
+
+    pub fn kick(&mut self, velocity: &mut Velocity, go_right: bool) {
+        velocity.x = if go_right { SHELL_SPEED } else { -SHELL_SPEED };
+        self.grace.reset();
+        self.combo = 0;
+    }
+
+    pub fn stop(&mut self, velocity: &mut Velocity) {
+        velocity.x = 0.0;
+        self.grace.reset();
+    }
+}
+
+pub fn move_shells(
+    mut commands: Commands,
+    time: Res<Time>,
+    physics: Res<PhysicsConfig>,
+    tilemap: Res<TileMap>,
+    mut shell_query: Query<(Entity, &mut Transform, &mut Velocity, &mut Shell), With<Enemy>>,
+    mut enemy_dead_event_writer: EventWriter<EnemyDead>,
+) {
+    let delta_seconds = time.delta_seconds();
+
+    for (entity, mut transform, mut velocity, mut shell) in shell_query.iter_mut() {
+        shell.grace.tick(time.delta());
+
+        velocity.y = (velocity.y - physics.gravity * delta_seconds).max(-physics.max_fall_speed);
+
+        let collision =
+            tilemap.move_and_collide(&mut transform.translation, SHELL_SIZE, velocity.0 * delta_seconds);
+
+        if collision.left || collision.right {
+            velocity.x = -velocity.x;
+        }
+
+        if collision.bottom || collision.top {
+            velocity.y = 0.0;
+        }
+
+        if transform.translation.y + SHELL_SIZE.y / 2.0 < tilemap.bottom() {
+            commands.entity(entity).despawn();
+            enemy_dead_event_writer.send(EnemyDead);
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub fn shell_hit_enemies(
+    mut commands: Commands,
+    mut enemy_collision_event_reader: EventReader<EnemyCollisionEvent>,
+    mut enemy_query: Query<(&Transform, Option<(&mut Shell, &Velocity)>), With<Enemy>>,
+    mut enemy_dead_event_writer: EventWriter<EnemyDead>,
+    mut score_event_writer: EventWriter<ScoreEvent>,
+    mut lives: ResMut<Lives>,
+) {
+    let mut hit_enemies: Vec<Entity> = Vec::new();
+
+    for event in enemy_collision_event_reader.iter() {
+        for (shell_entity, entity) in [(event.enemy, event.other), (event.other, event.enemy)] {
+            if hit_enemies.contains(&shell_entity) || hit_enemies.contains(&entity) {
+                continue;
+            }
+
+            let position = match enemy_query.get(entity) {
+                Ok((transform, _)) => transform.translation,
+                Err(_) => continue,
+            };
+            let (mut shell, velocity) = match enemy_query.get_mut(shell_entity) {
+                Ok((_, Some((shell, velocity)))) if Shell::is_sliding(velocity) => (shell, velocity),
+                _ => continue,
+            };
+
+            hit_enemies.push(entity);
+            knock_out_enemy(&mut commands, entity, velocity.x > 0.0);
+            enemy_dead_event_writer.send(EnemyDead);
+
+            match SHELL_COMBO_POINTS.get(shell.combo) {
+                Some(points) => score_event_writer.send(ScoreEvent {
+                    points: *points,
+                    x: position.x,
+                    y: position.y,
+                }),
+                None => **lives += 1,
+            }
+            shell.combo += 1;
+        }
+    }
+}
@@ -0,0 +1,230 @@
+use bevy::{
+    audio::AudioSink,
+    prelude::{
+        AssetServer, Assets, Audio, Color, Commands, Component, Entity, EventReader, EventWriter,
+        Handle, PlaybackSettings, Query, Res, ResMut, Resource, Transform, Vec2, With, default,
+    },
+    sprite::{SpriteSheetBundle, TextureAtlas, TextureAtlasSprite},
+    time::{Time, Timer, TimerMode},
+};
+
+use crate::{
+    collision::{Collider, MarioItemCollisionEvent},
+    enemy::MarioLevelMusicController,
+    game_state::LevelScoped,
+    mario::Mario,
+    physics::{PhysicsConfig, Velocity},
+    score::{ScoreEvent, POWER_UP_POINTS},
+    tilemap::TileMap,
+};
+
+const STAR_SIZE: Vec2 = Vec2::new(16.0, 16.0);
+const STAR_SPEED: f32 = 60.0;
+const STAR_BOUNCE_SPEED: f32 = 250.0;
+/// How long Mario stays invincible after touching a star.
+const STARMAN_DURATION: f32 = 10.0;
+const STARMAN_PALETTE_CYCLE: f32 = 0.05;
+const STARMAN_PALETTE: [Color; 4] = [
+    Color::WHITE,
+    Color::rgb(1.0, 0.5, 0.5),
+    Color::rgb(0.5, 1.0, 0.5),
+    Color::rgb(0.5, 0.6, 1.0),
+];
+const STAR_MUSIC: &str = "star_music.wav";
+
+/// Rises out of its block, then bounces along the level until Mario catches
+/// it or it falls out.
+#[derive(Component)]
+pub struct Star {
+    pub is_released: bool,
+}
+
+/// `x` and `y` are the centre of the question block the star comes out of.
+pub struct StarReleaseEvent {
+    pub x: f32,
+    pub y: f32,
+}
+
+/// Given to Mario when he touches a star. While present every enemy he
+/// touches dies and his palette cycles; it removes itself once `timer` is done.
+#[derive(Component)]
+pub struct Starman {
+    timer: Timer,
+    palette: Timer,
+    palette_index: usize,
+}
+
+impl Default for Starman {
+    fn default() -> Self {
+        Starman {
+            timer: Timer::from_seconds(STARMAN_DURATION, TimerMode::Once),
+            palette: Timer::from_seconds(STARMAN_PALETTE_CYCLE, TimerMode::Repeating),
+            palette_index: 0,
+        }
+    }
+}
+
+/// The star theme, which takes over from the level music while Mario is
+/// invincible.
+#[derive(Resource, Default)]
+pub struct StarMusicController(pub Handle<AudioSink>);
+
+impl StarMusicController {
+    pub fn stop(&mut self, audio_sinks: &Assets<AudioSink>) {
+        if let Some(sink) = audio_sinks.get(&self.0) {
+            sink.stop();
+        }
+        self.0 = Handle::default();
+    }
+}
+
+pub fn get_star_bundle(
+    asset_server: &AssetServer,
+    texture_atlases: &mut Assets<TextureAtlas>,
+    x: f32,
+    y: f32,
+) -> (SpriteSheetBundle, Velocity, Collider, Star) {
+    let star_texture_atlas =
+        TextureAtlas::from_grid(asset_server.load("star.png"), STAR_SIZE, 1, 1, None, None);
+
+    (
+        SpriteSheetBundle {
+            texture_atlas: texture_atlases.add(star_texture_atlas),
+            transform: Transform::from_xyz(x, y, 2.5),
+            ..default()
+        },
+        Velocity::default(),
+        Collider::new(STAR_SIZE),
+        Star { is_released: false },
+    )
+}
+
+pub fn star_release_event_read(
+    mut commands: Commands,
+    mut star_release_event_reader: EventReader<StarReleaseEvent>,
+    asset_server: Res<AssetServer>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+) {
+    for event in star_release_event_reader.iter() {
+        commands.spawn((
+            get_star_bundle(&asset_server, &mut texture_atlases, event.x, event.y),
+            LevelScoped,
+        ));
+    }
+}
+
+pub fn move_stars(
+    mut commands: Commands,
+    time: Res<Time>,
+    physics: Res<PhysicsConfig>,
+    tilemap: Res<TileMap>,
+    mut star_query: Query<(Entity, &mut Transform, &mut Velocity, &mut Star)>,
+) {
+    let delta_seconds = time.delta_seconds();
+
+    for (entity, mut transform, mut velocity, mut star) in star_query.iter_mut() {
+        if !star.is_released {
+            transform.translation.y += physics.item_rise_speed * delta_seconds;
+            if !tilemap.overlaps_solid(transform.translation, STAR_SIZE) {
+                star.is_released = true;
+                velocity.0 = Vec2::new(STAR_SPEED, STAR_BOUNCE_SPEED);
+            }
+            continue;
+        }
+
+        velocity.y -= physics.projectile_gravity * delta_seconds;
+
+        let collision =
+            tilemap.move_and_collide(&mut transform.translation, STAR_SIZE, velocity.0 * delta_seconds);
+
+        if collision.bottom {
+            velocity.y = STAR_BOUNCE_SPEED;
+        } else if collision.top {
+            velocity.y = 0.0;
+        }
+
+        if collision.left || collision.right {
+            velocity.x = -velocity.x;
+        }
+
+        if transform.translation.y + STAR_SIZE.y / 2.0 < tilemap.bottom() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn collect_stars(
+    mut commands: Commands,
+    mut mario_item_collision_event_reader: EventReader<MarioItemCollisionEvent>,
+    mario_query: Query<Entity, With<Mario>>,
+    star_query: Query<&Transform, With<Star>>,
+    mut score_event_writer: EventWriter<ScoreEvent>,
+    asset_server: Res<AssetServer>,
+    audio: Res<Audio>,
+    audio_sinks: Res<Assets<AudioSink>>,
+    music_controller: Res<MarioLevelMusicController>,
+    mut star_music_controller: ResMut<StarMusicController>,
+) {
+    let mario = match mario_query.get_single() {
+        Ok(mario) => mario,
+        Err(_) => return,
+    };
+
+    for event in mario_item_collision_event_reader.iter() {
+        let transform = match star_query.get(event.item) {
+            Ok(transform) => transform,
+            Err(_) => continue,
+        };
+
+        commands.entity(event.item).despawn();
+        commands.entity(mario).insert(Starman::default());
+        audio.play(asset_server.load("powerup.ogg"));
+        score_event_writer.send(ScoreEvent {
+            points: POWER_UP_POINTS,
+            x: transform.translation.x,
+            y: transform.translation.y,
+        });
+
+        if let Some(sink) = audio_sinks.get(&music_controller.0) {
+            sink.pause();
+        }
+        star_music_controller.stop(&audio_sinks);
+
+        let star_music = audio.play_with_settings(asset_server.load(STAR_MUSIC), PlaybackSettings::LOOP);
+        star_music_controller.0 = audio_sinks.get_handle(star_music);
+    }
+}
+
+/// Cycles Mario's palette while he is invincible and hands the music back to
+/// the level once the star wears off.
+pub fn tick_starman(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut mario_query: Query<(Entity, &mut TextureAtlasSprite, &mut Starman)>,
+    audio_sinks: Res<Assets<AudioSink>>,
+    music_controller: Res<MarioLevelMusicController>,
+    mut star_music_controller: ResMut<StarMusicController>,
+) {
+    for (entity, mut sprite, mut starman) in mario_query.iter_mut() {
+        if starman.timer.tick(time.delta()).finished() {
+            sprite.color = Color::WHITE;
+            commands.entity(entity).remove::<Starman>();
+            star_music_controller.stop(&audio_sinks);
+
+            if let Some(sink) = audio_sinks.get(&music_controller.0) {
+                sink.play();
+            }
+        } else if starman.palette.tick(time.delta()).just_finished() {
+            starman.palette_index = (starman.palette_index + 1) % STARMAN_PALETTE.len();
+            sprite.color = STARMAN_PALETTE[starman.palette_index];
+        }
+    }
+}
+
+pub fn stop_star_music(
+    audio_sinks: Res<Assets<AudioSink>>,
+    mut star_music_controller: ResMut<StarMusicController>,
+) {
+    star_music_controller.stop(&audio_sinks);
+}
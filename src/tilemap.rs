@@ -0,0 +1,326 @@
+use bevy::prelude::{Resource, Vec2, Vec3};
+
+pub const TILE_SIZE: f32 = 16.0;
+
+// Keeps a box that rests exactly on a tile edge from counting as overlapping it.
+const EPSILON: f32 = 0.01;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Tile {
+    Empty,
+    Solid,
+    /// An invisible block, which only stops things moving up into it.
+    Hidden,
+}
+
+impl Tile {
+    pub fn from_char(character: char) -> Option<Tile> {
+        match character {
+            '.' => Some(Tile::Empty),
+            '#' => Some(Tile::Solid),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Default, Clone, Copy)]
+pub struct TileCollision {
+    pub left: bool,
+    pub right: bool,
+    pub top: bool,
+    pub bottom: bool,
+}
+
+/// Collision grid of the current level. `origin` is the world position of the
+/// top-left corner of the first tile; columns grow to the right and rows grow
+/// downwards. Outside the grid, the left and right sides are walls while
+/// everything above and below is open, so anything can fall out of the level.
+#[derive(Resource, Default)]
+pub struct TileMap {
+    origin: Vec2,
+    width: i32,
+    height: i32,
+    tiles: Vec<Tile>,
+}
+
+impl TileMap {
+    pub fn new(origin: Vec2, rows: &[String]) -> TileMap {
+        let width = rows.iter().map(|row| row.chars().count()).max().unwrap_or(0);
+        let mut tiles = Vec::with_capacity(width * rows.len());
+
+        for row in rows.iter() {
+            let mut row_tiles: Vec<Tile> = row
+                .chars()
+                .map(|character| Tile::from_char(character).unwrap_or(Tile::Empty))
+                .collect();
+            row_tiles.resize(width, Tile::Empty);
+            tiles.extend(row_tiles);
+        }
+
+        TileMap {
+            origin,
+            width: width as i32,
+            height: rows.len() as i32,
+            tiles,
+        }
+    }
+
+    pub fn left(&self) -> f32 {
+        self.origin.x
+    }
+
+    pub fn right(&self) -> f32 {
+        self.origin.x + self.width as f32 * TILE_SIZE
+    }
+
+    /// World Y of the bottom edge of the grid; anything below it has fallen out.
+    pub fn bottom(&self) -> f32 {
+        self.origin.y - self.height as f32 * TILE_SIZE
+    }
+
+    pub fn column_at(&self, x: f32) -> i32 {
+        ((x - self.origin.x) / TILE_SIZE).floor() as i32
+    }
+
+    pub fn row_at(&self, y: f32) -> i32 {
+        ((self.origin.y - y) / TILE_SIZE).floor() as i32
+    }
+
+    pub fn is_solid(&self, column: i32, row: i32) -> bool {
+        if column < 0 || column >= self.width {
+            return true;
+        }
+        if row < 0 || row >= self.height {
+            return false;
+        }
+
+        self.tiles[(row * self.width + column) as usize] == Tile::Solid
+    }
+
+    fn is_solid_from_below(&self, column: i32, row: i32) -> bool {
+        self.is_solid(column, row)
+            || (column >= 0
+                && column < self.width
+                && row >= 0
+                && row < self.height
+                && self.tiles[(row * self.width + column) as usize] == Tile::Hidden)
+    }
+
+    pub fn set_tile_at(&mut self, x: f32, y: f32, tile: Tile) {
+        let column = self.column_at(x);
+        let row = self.row_at(y);
+
+        if column >= 0 && column < self.width && row >= 0 && row < self.height {
+            self.tiles[(row * self.width + column) as usize] = tile;
+        }
+    }
+
+    pub fn overlaps_solid(&self, translation: Vec3, size: Vec2) -> bool {
+        let half = size / 2.0;
+
+        for column in self.column_at(translation.x - half.x + EPSILON)
+            ..=self.column_at(translation.x + half.x - EPSILON)
+        {
+            for row in self.row_at(translation.y + half.y - EPSILON)
+                ..=self.row_at(translation.y - half.y + EPSILON)
+            {
+                if self.is_solid(column, row) {
+                    return true;
+                }
+            }
+        }
+
+        false
+    }
+
+    /// Moves a box of `size` centred on `translation` by `delta`, first along X
+    /// and then along Y, stopping it flush against the first solid tile it runs
+    /// into. Tiles the box already overlaps are ignored so it can never get stuck.
+    pub fn move_and_collide(&self, translation: &mut Vec3, size: Vec2, delta: Vec2) -> TileCollision {
+        let mut collision = TileCollision::default();
+        let half = size / 2.0;
+
+        if delta.x != 0.0 {
+            let top_row = self.row_at(translation.y + half.y - EPSILON);
+            let bottom_row = self.row_at(translation.y - half.y + EPSILON);
+            let is_column_solid =
+                |column: i32| (top_row..=bottom_row).any(|row| self.is_solid(column, row));
+
+            translation.x += delta.x;
+
+            if delta.x > 0.0 {
+                let edge = translation.x - delta.x + half.x;
+                let first = ((edge - self.origin.x) / TILE_SIZE - EPSILON).ceil() as i32;
+                let last = ((edge + delta.x - self.origin.x) / TILE_SIZE).ceil() as i32 - 1;
+
+                if let Some(column) = (first..=last).find(|column| is_column_solid(*column)) {
+                    translation.x = self.origin.x + column as f32 * TILE_SIZE - half.x;
+                    collision.right = true;
+                }
+            } else {
+                let edge = translation.x - delta.x - half.x;
+                let first = ((edge - self.origin.x) / TILE_SIZE + EPSILON).floor() as i32 - 1;
+                let last = ((edge + delta.x - self.origin.x) / TILE_SIZE).floor() as i32;
+
+                if let Some(column) = (last..=first).rev().find(|column| is_column_solid(*column)) {
+                    translation.x = self.origin.x + (column + 1) as f32 * TILE_SIZE + half.x;
+                    collision.left = true;
+                }
+            }
+        }
+
+        if delta.y != 0.0 {
+            let left_column = self.column_at(translation.x - half.x + EPSILON);
+            let right_column = self.column_at(translation.x + half.x - EPSILON);
+            let is_row_solid = |row: i32| {
+                (left_column..=right_column).any(|column| {
+                    if delta.y > 0.0 {
+                        self.is_solid_from_below(column, row)
+                    } else {
+                        self.is_solid(column, row)
+                    }
+                })
+            };
+
+            translation.y += delta.y;
+
+            if delta.y < 0.0 {
+                let edge = translation.y - delta.y - half.y;
+                let first = ((self.origin.y - edge) / TILE_SIZE - EPSILON).ceil() as i32;
+                let last = ((self.origin.y - edge - delta.y) / TILE_SIZE).ceil() as i32 - 1;
+
+                if let Some(row) = (first..=last).find(|row| is_row_solid(*row)) {
+                    translation.y = self.origin.y - row as f32 * TILE_SIZE + half.y;
+                    collision.bottom = true;
+                }
+            } else {
+                let edge = translation.y - delta.y + half.y;
+                let first = ((self.origin.y - edge) / TILE_SIZE + EPSILON).floor() as i32 - 1;
+                let last = ((self.origin.y - edge - delta.y) / TILE_SIZE).floor() as i32;
+
+                if let Some(row) = (last..=first).rev().find(|row| is_row_solid(*row)) {
+                    translation.y = self.origin.y - (row + 1) as f32 * TILE_SIZE - half.y;
+                    collision.top = true;
+                }
+            }
+        }
+
+        collision
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: Vec2 = Vec2::new(16.0, 16.0);
+
+    // Rows span Y [-16 * (row + 1), -16 * row] and columns span X
+    // [16 * column, 16 * (column + 1)], so a tile's centre is at
+    // (16 * column + 8, -16 * row - 8).
+    fn tilemap() -> TileMap {
+        let rows = ["......", "..#...", "......", "######"].map(String::from);
+        TileMap::new(Vec2::ZERO, &rows)
+    }
+
+    #[test]
+    fn lands_on_floor() {
+        let mut translation = Vec3::new(8.0, -36.0, 0.0);
+
+        let collision = tilemap().move_and_collide(&mut translation, SIZE, Vec2::new(0.0, -10.0));
+
+        assert!(collision.bottom);
+        assert!(!collision.top && !collision.left && !collision.right);
+        assert_eq!(translation.y, -40.0);
+    }
+
+    #[test]
+    fn lands_on_floor_from_further_than_a_tile_away() {
+        let mut translation = Vec3::new(88.0, -8.0, 0.0);
+
+        let collision = tilemap().move_and_collide(&mut translation, SIZE, Vec2::new(0.0, -50.0));
+
+        assert!(collision.bottom);
+        assert_eq!(translation.y, -40.0);
+    }
+
+    #[test]
+    fn falls_freely_through_empty_tiles() {
+        let mut translation = Vec3::new(8.0, -8.0, 0.0);
+
+        let collision = tilemap().move_and_collide(&mut translation, SIZE, Vec2::new(0.0, -10.0));
+
+        assert!(!collision.bottom);
+        assert_eq!(translation.y, -18.0);
+    }
+
+    #[test]
+    fn stops_against_wall_on_the_right() {
+        let mut translation = Vec3::new(8.0, -24.0, 0.0);
+
+        let collision = tilemap().move_and_collide(&mut translation, SIZE, Vec2::new(20.0, 0.0));
+
+        assert!(collision.right);
+        assert!(!collision.left);
+        assert_eq!(translation.x, 24.0);
+    }
+
+    #[test]
+    fn stops_against_wall_on_the_left() {
+        let mut translation = Vec3::new(72.0, -24.0, 0.0);
+
+        let collision = tilemap().move_and_collide(&mut translation, SIZE, Vec2::new(-30.0, 0.0));
+
+        assert!(collision.left);
+        assert!(!collision.right);
+        assert_eq!(translation.x, 56.0);
+    }
+
+    #[test]
+    fn stops_at_the_left_edge_of_the_grid() {
+        let mut translation = Vec3::new(10.0, -24.0, 0.0);
+
+        let collision = tilemap().move_and_collide(&mut translation, SIZE, Vec2::new(-5.0, 0.0));
+
+        assert!(collision.left);
+        assert_eq!(translation.x, 8.0);
+    }
+
+    #[test]
+    fn bumps_into_ceiling() {
+        let mut translation = Vec3::new(40.0, -40.0, 0.0);
+
+        let collision = tilemap().move_and_collide(&mut translation, SIZE, Vec2::new(0.0, 10.0));
+
+        assert!(collision.top);
+        assert!(!collision.bottom);
+        assert_eq!(translation.y, -40.0);
+    }
+
+    #[test]
+    fn slides_along_floor_while_resting_on_it() {
+        let mut translation = Vec3::new(8.0, -40.0, 0.0);
+
+        let collision = tilemap().move_and_collide(&mut translation, SIZE, Vec2::new(5.0, -1.0));
+
+        assert!(collision.bottom);
+        assert!(!collision.right);
+        assert_eq!(translation, Vec3::new(13.0, -40.0, 0.0));
+    }
+
+    #[test]
+    fn hidden_tile_only_stops_upward_movement() {
+        let mut tilemap = tilemap();
+        tilemap.set_tile_at(72.0, -24.0, Tile::Hidden);
+
+        let mut falling = Vec3::new(72.0, -8.0, 0.0);
+        let collision = tilemap.move_and_collide(&mut falling, SIZE, Vec2::new(0.0, -10.0));
+        assert!(!collision.bottom);
+        assert_eq!(falling.y, -18.0);
+
+        let mut jumping = Vec3::new(72.0, -40.0, 0.0);
+        let collision = tilemap.move_and_collide(&mut jumping, SIZE, Vec2::new(0.0, 10.0));
+        assert!(collision.top);
+        assert_eq!(jumping.y, -40.0);
+    }
+}
@@ -0,0 +1,130 @@
+use bevy::{
+    prelude::{
+        AssetServer, Assets, Commands, Component, Entity, EventReader, Query, Res, ResMut,
+        Transform, TransformBundle, Vec2, Without, default,
+    },
+    sprite::{SpriteSheetBundle, TextureAtlas},
+    time::Time,
+};
+
+use crate::{collision::Collider, game_state::LevelScoped, tilemap::TILE_SIZE};
+
+const VINE_WIDTH: f32 = 16.0;
+const VINE_GROW_SPEED: f32 = 30.0;
+const VINE_MAX_LENGTH: f32 = 6.0 * TILE_SIZE;
+
+/// Grows up out of the top of its block and lets Mario climb it. The entity
+/// only carries the hitbox; the sprite is made of `VineSegment`s that slide
+/// up with the tip as it grows.
+#[derive(Component)]
+pub struct Vine {
+    bottom: f32,
+    length: f32,
+    segments: u32,
+}
+
+/// One tile of a vine's sprite, counted down from the tip.
+#[derive(Component)]
+pub struct VineSegment {
+    vine: Entity,
+    index: u32,
+}
+
+/// `x` and `y` are the centre of the question block the vine grows out of.
+pub struct VineReleaseEvent {
+    pub x: f32,
+    pub y: f32,
+}
+
+pub fn get_vine_bundle(x: f32, y: f32) -> (TransformBundle, Collider, Vine) {
+    let bottom = y + TILE_SIZE / 2.0;
+
+    (
+        TransformBundle::from_transform(Transform::from_xyz(x, bottom, 2.4)),
+        Collider::new(Vec2::new(VINE_WIDTH, 0.0)),
+        Vine {
+            bottom,
+            length: 0.0,
+            segments: 0,
+        },
+    )
+}
+
+pub fn get_vine_segment_bundle(
+    asset_server: &AssetServer,
+    texture_atlases: &mut Assets<TextureAtlas>,
+    vine: Entity,
+    index: u32,
+    x: f32,
+    y: f32,
+) -> (SpriteSheetBundle, VineSegment) {
+    let vine_texture_atlas = TextureAtlas::from_grid(
+        asset_server.load("vine.png"),
+        Vec2::new(TILE_SIZE, TILE_SIZE),
+        1,
+        1,
+        None,
+        None,
+    );
+
+    (
+        SpriteSheetBundle {
+            texture_atlas: texture_atlases.add(vine_texture_atlas),
+            transform: Transform::from_xyz(x, y, 2.4),
+            ..default()
+        },
+        VineSegment { vine, index },
+    )
+}
+
+pub fn vine_release_event_read(
+    mut commands: Commands,
+    mut vine_release_event_reader: EventReader<VineReleaseEvent>,
+) {
+    for event in vine_release_event_reader.iter() {
+        commands.spawn((get_vine_bundle(event.x, event.y), LevelScoped));
+    }
+}
+
+pub fn grow_vines(
+    mut commands: Commands,
+    time: Res<Time>,
+    asset_server: Res<AssetServer>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    mut vine_query: Query<(Entity, &mut Transform, &mut Collider, &mut Vine)>,
+    mut segment_query: Query<(&mut Transform, &VineSegment), Without<Vine>>,
+) {
+    for (entity, mut transform, mut collider, mut vine) in vine_query.iter_mut() {
+        if vine.length >= VINE_MAX_LENGTH {
+            continue;
+        }
+
+        vine.length = (vine.length + VINE_GROW_SPEED * time.delta_seconds()).min(VINE_MAX_LENGTH);
+        transform.translation.y = vine.bottom + vine.length / 2.0;
+        collider.size.y = vine.length;
+
+        // New segments start inside the block and are hidden behind it.
+        let top = vine.bottom + vine.length;
+        while top - vine.segments as f32 * TILE_SIZE > vine.bottom {
+            commands.spawn((
+                get_vine_segment_bundle(
+                    &asset_server,
+                    &mut texture_atlases,
+                    entity,
+                    vine.segments,
+                    transform.translation.x,
+                    top - (vine.segments as f32 + 0.5) * TILE_SIZE,
+                ),
+                LevelScoped,
+            ));
+            vine.segments += 1;
+        }
+    }
+
+    for (mut transform, segment) in segment_query.iter_mut() {
+        if let Ok((_, _, _, vine)) = vine_query.get(segment.vine) {
+            transform.translation.y =
+                vine.bottom + vine.length - (segment.index as f32 + 0.5) * TILE_SIZE;
+        }
+    }
+}